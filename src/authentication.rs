@@ -1,20 +1,38 @@
-use std::process::Command;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
 
-use anyhow::{bail, format_err, Result};
+use anyhow::{bail, format_err, Context, Result};
 use serde::{Deserialize, Serialize};
+use tokio::process::Command;
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum Secret {
     String(String),
+    /// STDOUT of the shell command
     Program(String),
+    /// Field of the JSON object printed by the shell command, so one command can provide both
+    /// username and token
+    ProgramJson {
+        command: String,
+        field: String,
+    },
+    /// Environment variable
+    Env(String),
+    /// Contents of the file. Path may start with "~"
+    File(String),
 }
 
+/// Resolved secrets are cached for the run, so programs are executed and files are read once.
+/// The lock guards only the map, values are resolved in the cells of their secrets
+static SECRETS: OnceLock<std::sync::Mutex<HashMap<Secret, Arc<tokio::sync::OnceCell<String>>>>> =
+    OnceLock::new();
+
 impl Secret {
-    fn of_command(command: &str) -> Result<String> {
+    async fn of_command(command: &str) -> Result<String> {
         let output = if cfg!(target_os = "windows") {
-            Command::new("cmd").args(["/C", command]).output()
+            Command::new("cmd").args(["/C", command]).output().await
         } else {
-            Command::new("sh").args(["-c", command]).output()
+            Command::new("sh").args(["-c", command]).output().await
         };
         let output =
             output.map_err(|err| format_err!("Failed to execute {:?}: {}", command, err))?;
@@ -32,12 +50,328 @@ impl Secret {
             .map(|data| data.to_string())
     }
 
-    pub fn get(&self) -> Result<String> {
+    fn of_json(command: &str, field: &str, output: &str) -> Result<String> {
+        let json: serde_json::Value = serde_json::from_str(output)
+            .map_err(|err| format_err!("Invalid JSON in command {:?} output: {}", command, err))?;
+        match json.get(field) {
+            None | Some(serde_json::Value::Null) => {
+                bail!(
+                    "Field {:?} not found in command {:?} output",
+                    field,
+                    command
+                )
+            }
+            Some(serde_json::Value::String(v)) => Ok(v.clone()),
+            Some(v) => Ok(v.to_string()),
+        }
+    }
+
+    /// Value of the secret, the whole output of the program for `ProgramJson`
+    async fn resolve(&self) -> Result<String> {
         match self {
             Secret::String(v) => Ok(v.clone()),
-            Secret::Program(command) => Self::of_command(command),
+            Secret::Program(command) | Secret::ProgramJson { command, .. } => {
+                Self::of_command(command).await
+            }
+            Secret::Env(name) => std::env::var(name).map_err(|err| {
+                format_err!("Failed to read environment variable {:?}: {}", name, err)
+            }),
+            Secret::File(path) => {
+                let path = shellexpand::tilde(path).to_string();
+                tokio::fs::read_to_string(&path)
+                    .await
+                    .map_err(|err| format_err!("Failed to read secret file {:?}: {}", path, err))
+            }
+        }
+    }
+
+    async fn cached(&self) -> Result<String> {
+        let cell = SECRETS
+            .get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+            .lock()
+            .map_err(|_| format_err!("Secrets cache is poisoned"))?
+            .entry(self.clone())
+            .or_default()
+            .clone();
+        cell.get_or_try_init(|| self.resolve()).await.cloned()
+    }
+
+    /// Value without trailing line breaks
+    pub async fn get(&self) -> Result<String> {
+        let value = match self {
+            Secret::String(v) => v.clone(),
+            // JSON is parsed from the cached output of its program
+            Secret::ProgramJson { command, field } => Self::of_json(
+                command,
+                field,
+                &Secret::Program(command.clone()).cached().await?,
+            )?,
+            _ => self.cached().await?,
+        };
+        Ok(value.trim_end_matches(['\r', '\n']).to_owned())
+    }
+}
+
+/// Plain value or a secret, e.g. username printed by the same program as the token
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Username {
+    Plain(String),
+    Secret(#[serde(with = "serde_yaml::with::singleton_map")] Secret),
+}
+
+impl Username {
+    pub async fn get(&self) -> Result<String> {
+        match self {
+            Username::Plain(v) => Ok(v.clone()),
+            Username::Secret(v) => v.get().await,
+        }
+    }
+}
+
+/// OAuth 2.0 client refreshing access tokens with the refresh token grant
+#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+pub struct OAuth2 {
+    /// Token endpoint, https://auth.atlassian.com/oauth/token for Atlassian Cloud
+    pub token_url: url::Url,
+    pub client_id: String,
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    pub client_secret: Secret,
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    pub refresh_token: Secret,
+}
+
+#[derive(Deserialize)]
+struct OAuth2TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    /// Set when the server rotates refresh tokens
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+struct OAuth2Token {
+    access_token: String,
+    refresh_token: String,
+    expires_at: std::time::Instant,
+}
+
+/// Access tokens are shared by all requests of the run and refreshed shortly before expiration
+static OAUTH2_TOKENS: OnceLock<tokio::sync::Mutex<HashMap<OAuth2, OAuth2Token>>> = OnceLock::new();
+
+impl OAuth2 {
+    async fn refresh(
+        &self,
+        client: &reqwest::Client,
+        refresh_token: String,
+    ) -> Result<OAuth2Token> {
+        slog_scope::info!("Refreshing OAuth 2.0 access token at {}", self.token_url);
+
+        let response = client
+            .post(self.token_url.clone())
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", &self.client_secret.get().await?),
+                ("refresh_token", refresh_token.as_str()),
+            ])
+            .send()
+            .await?;
+        let response =
+            crate::error::ApiError::check(&self.token_url, reqwest::Method::POST, response)
+                .await
+                .map_err(|err| crate::error::ApiError {
+                    kind: crate::error::ErrorKind::Auth,
+                    ..err
+                })
+                .with_context(|| {
+                    format!(
+                        "Failed to refresh OAuth 2.0 access token of {}",
+                        self.client_id
+                    )
+                })?;
+        let token: OAuth2TokenResponse = serde_json::from_str(&response.text().await?)?;
+
+        Ok(OAuth2Token {
+            access_token: token.access_token,
+            refresh_token: token.refresh_token.unwrap_or(refresh_token),
+            expires_at: std::time::Instant::now()
+                + std::time::Duration::from_secs(token.expires_in.unwrap_or(3600)),
+        })
+    }
+
+    /// Valid access token. Refresh token rotated by the server is cached on disk and used
+    /// instead of the configured one, the configured one is tried if the cached one is rejected
+    pub async fn access_token(&self, client: &reqwest::Client) -> Result<String> {
+        let margin = std::time::Duration::from_secs(60);
+        let mut tokens = OAUTH2_TOKENS
+            .get_or_init(|| tokio::sync::Mutex::new(HashMap::new()))
+            .lock()
+            .await;
+        let (refresh_token, from_cache) = match tokens.get(self) {
+            Some(token) if token.expires_at > std::time::Instant::now() + margin => {
+                return Ok(token.access_token.clone())
+            }
+            Some(token) => (token.refresh_token.clone(), false),
+            None => match read_cache("oauth2", &self.token_url, &self.client_id) {
+                Some(cached) => (cached, true),
+                None => (self.refresh_token.get().await?, false),
+            },
+        };
+        let token = match self.refresh(client, refresh_token.clone()).await {
+            Ok(v) => v,
+            Err(err) if from_cache => {
+                let configured = self.refresh_token.get().await?;
+                if configured == refresh_token {
+                    return Err(err);
+                }
+                slog_scope::warn!("Cached refresh token is rejected: {:#}", err);
+                self.refresh(client, configured).await?
+            }
+            Err(err) => return Err(err),
+        };
+        if token.refresh_token != refresh_token {
+            if let Err(err) = write_cache(
+                "oauth2",
+                &self.token_url,
+                &self.client_id,
+                &token.refresh_token,
+            ) {
+                slog_scope::warn!("{}", err);
+            }
+        }
+        let access_token = token.access_token.clone();
+        let _ = tokens.insert(self.clone(), token);
+        Ok(access_token)
+    }
+}
+
+#[derive(Deserialize)]
+struct SessionLoginResponse {
+    session: SessionCookie,
+}
+
+#[derive(Deserialize)]
+struct SessionCookie {
+    name: String,
+    value: String,
+}
+
+/// Session cookies by base URL and username
+static SESSIONS: OnceLock<tokio::sync::Mutex<HashMap<(url::Url, String), String>>> =
+    OnceLock::new();
+
+/// Session cookies and rotated refresh tokens are stored in the user cache directory to be
+/// reused by next runs
+fn cache_path(kind: &str, url: &url::Url, username: &str) -> Option<std::path::PathBuf> {
+    let cache_dir = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(v) => std::path::PathBuf::from(v),
+        None => std::path::PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    let name = format!("{}_{}", url, username).replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    Some(cache_dir.join("uprava").join(format!("{}_{}", kind, name)))
+}
+
+fn read_cache(kind: &str, url: &url::Url, username: &str) -> Option<String> {
+    let path = cache_path(kind, url, username)?;
+    let value = std::fs::read_to_string(path).ok()?;
+    Some(value.trim().to_owned()).filter(|v| !v.is_empty())
+}
+
+/// Cache file is readable by the user only
+fn write_cache(kind: &str, url: &url::Url, username: &str, value: &str) -> Result<()> {
+    use std::io::Write;
+
+    let path = match cache_path(kind, url, username) {
+        None => return Ok(()),
+        Some(v) => v,
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    let _ = options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let _ = options.mode(0o600);
+    }
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(value.as_bytes()))
+        .map_err(|err| format_err!("Failed to write {} cache {:?}: {}", kind, path, err))
+}
+
+async fn session_login(
+    client: &reqwest::Client,
+    base_url: &url::Url,
+    username: &str,
+    password: &Secret,
+) -> Result<String> {
+    let url = crate::http::api_url(base_url, "/rest/auth/1/session");
+    slog_scope::info!("Logging in to {} as {}", url, username);
+
+    let response = client
+        .post(url.clone())
+        .header(reqwest::header::ACCEPT, "application/json")
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_string(&serde_json::json!({
+            "username": username,
+            "password": password.get().await?,
+        }))?)
+        .send()
+        .await?;
+    let response = crate::error::ApiError::check(base_url, reqwest::Method::POST, response)
+        .await
+        .map_err(|err| crate::error::ApiError {
+            kind: crate::error::ErrorKind::Auth,
+            ..err
+        })
+        .with_context(|| format!("Failed to log in as {}", username))?;
+    let login: SessionLoginResponse = serde_json::from_str(&response.text().await?)?;
+
+    Ok(format!("{}={}", login.session.name, login.session.value))
+}
+
+/// Cached session cookie. Logs in again if there is no cookie or the `expired` one is still
+/// cached
+async fn session_cookie(
+    client: &reqwest::Client,
+    base_url: &url::Url,
+    username: &str,
+    password: &Secret,
+    expired: Option<&str>,
+) -> Result<String> {
+    let mut sessions = SESSIONS
+        .get_or_init(|| tokio::sync::Mutex::new(HashMap::new()))
+        .lock()
+        .await;
+    let key = (base_url.clone(), username.to_owned());
+    let cached = sessions
+        .get(&key)
+        .cloned()
+        .or_else(|| read_cache("session", base_url, username));
+    if let Some(cookie) = cached {
+        if Some(cookie.as_str()) != expired {
+            let _ = sessions.insert(key, cookie.clone());
+            return Ok(cookie);
         }
     }
+
+    let cookie = session_login(client, base_url, username, password).await?;
+    if let Err(err) = write_cache("session", base_url, username, &cookie) {
+        slog_scope::warn!("{}", err);
+    }
+    let _ = sessions.insert(key, cookie.clone());
+    Ok(cookie)
+}
+
+fn cookie_header(cookie: &str) -> Result<reqwest::header::HeaderValue> {
+    let mut cookie = reqwest::header::HeaderValue::from_str(cookie)?;
+    cookie.set_sensitive(true);
+    Ok(cookie)
 }
 
 #[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
@@ -46,4 +380,288 @@ pub enum Access {
     Token(Secret),
     #[serde(with = "serde_yaml::with::singleton_map")]
     JSessionID(Secret),
+    /// Username and password, or e-mail and API token for Atlassian Cloud
+    Basic {
+        username: Username,
+        #[serde(with = "serde_yaml::with::singleton_map")]
+        password: Secret,
+    },
+    OAuth2(OAuth2),
+    /// Jira session started by login with username and password. Session cookie is cached on disk
+    /// and renewed when it expires
+    Session {
+        username: Username,
+        #[serde(with = "serde_yaml::with::singleton_map")]
+        password: Secret,
+    },
+}
+
+impl Access {
+    async fn send_in_session(
+        client: &reqwest::Client,
+        base_url: &url::Url,
+        username: &str,
+        password: &Secret,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let retry = request.try_clone();
+        let cookie = session_cookie(client, base_url, username, password, None).await?;
+        let response = request
+            .header(reqwest::header::COOKIE, cookie_header(&cookie)?)
+            .send()
+            .await?;
+
+        match retry {
+            Some(retry) if response.status() == reqwest::StatusCode::UNAUTHORIZED => {
+                slog_scope::info!("Session of {} at {} is expired", username, base_url);
+                let cookie =
+                    session_cookie(client, base_url, username, password, Some(&cookie)).await?;
+                Ok(retry
+                    .header(reqwest::header::COOKIE, cookie_header(&cookie)?)
+                    .send()
+                    .await?)
+            }
+            _ => Ok(response),
+        }
+    }
+
+    /// Sends the request with credentials. With `Session` access the request is retried once
+    /// after a new login if the server responds with 401
+    pub async fn send(
+        &self,
+        client: &reqwest::Client,
+        base_url: &url::Url,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let request = match self {
+            Access::Token(secret) => request.bearer_auth(secret.get().await?),
+            Access::JSessionID(secret) => request.header(
+                reqwest::header::COOKIE,
+                cookie_header(&format!("JSESSIONID={}", secret.get().await?))?,
+            ),
+            Access::Basic { username, password } => {
+                request.basic_auth(username.get().await?, Some(password.get().await?))
+            }
+            Access::OAuth2(oauth2) => request.bearer_auth(oauth2.access_token(client).await?),
+            Access::Session { username, password } => {
+                return Self::send_in_session(
+                    client,
+                    base_url,
+                    &username.get().await?,
+                    password,
+                    request,
+                )
+                .await
+            }
+        };
+        Ok(request.send().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Access, OAuth2, Secret, Username};
+    use crate::http::test_server;
+
+    /// Cache files of the tests are written to a temporary directory
+    fn cache_dir() -> &'static std::path::Path {
+        static DIR: std::sync::OnceLock<tempfile::TempDir> = std::sync::OnceLock::new();
+        DIR.get_or_init(|| {
+            let dir = tempfile::tempdir().unwrap();
+            std::env::set_var("XDG_CACHE_HOME", dir.path());
+            dir
+        })
+        .path()
+    }
+
+    fn oauth2(server: &test_server::TestServer, client_id: &str) -> OAuth2 {
+        OAuth2 {
+            token_url: server.url.join("oauth/token").unwrap(),
+            client_id: client_id.to_owned(),
+            client_secret: Secret::String("secret".to_owned()),
+            refresh_token: Secret::String("configured".to_owned()),
+        }
+    }
+
+    fn form_value(body: &str, name: &str) -> Option<String> {
+        url::form_urlencoded::parse(body.as_bytes())
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.to_string())
+    }
+
+    async fn get(server: &test_server::TestServer, access: &Access) -> reqwest::StatusCode {
+        let client = reqwest::Client::new();
+        let url = server.url.join("rest/api/2/myself").unwrap();
+        access
+            .send(&client, &server.url, client.get(url))
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn secret_program_runs_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("runs");
+        let command = format!(
+            "echo run >> {:?}; sleep 0.2; echo '{{\"user\": \"u\", \"token\": 1}}'",
+            log
+        );
+        let token = Secret::ProgramJson {
+            command: command.clone(),
+            field: "token".to_owned(),
+        };
+        let user = Secret::ProgramJson {
+            command: command.clone(),
+            field: "user".to_owned(),
+        };
+        let program = Secret::Program(command);
+        let (token, user, output) = tokio::join!(token.get(), user.get(), program.get());
+        assert_eq!(token.unwrap(), "1");
+        assert_eq!(user.unwrap(), "u");
+        assert_eq!(output.unwrap(), r#"{"user": "u", "token": 1}"#);
+        assert_eq!(std::fs::read_to_string(log).unwrap(), "run\n");
+    }
+
+    #[tokio::test]
+    async fn basic_auth() {
+        let server = test_server::start(vec![(200, "{}")]).await;
+        let access = Access::Basic {
+            username: Username::Plain("user".to_owned()),
+            password: Secret::String("password".to_owned()),
+        };
+        assert_eq!(get(&server, &access).await, reqwest::StatusCode::OK);
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Basic dXNlcjpwYXNzd29yZA==")
+        );
+    }
+
+    #[tokio::test]
+    async fn oauth2_refresh_rotates_token() {
+        let _ = cache_dir();
+        let server = test_server::start(vec![
+            (
+                200,
+                r#"{"access_token": "access-1", "expires_in": 30, "refresh_token": "rotated-1"}"#,
+            ),
+            (200, "{}"),
+            (
+                200,
+                r#"{"access_token": "access-2", "expires_in": 3600, "refresh_token": "rotated-2"}"#,
+            ),
+            (200, "{}"),
+            (200, "{}"),
+        ])
+        .await;
+        let oauth2 = oauth2(&server, "rotating");
+        let access = Access::OAuth2(oauth2.clone());
+
+        // The first token expires within the margin, so the second request refreshes it again
+        for _ in 0..3 {
+            assert_eq!(get(&server, &access).await, reqwest::StatusCode::OK);
+        }
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 5);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/oauth/token");
+        assert_eq!(
+            form_value(&requests[0].body, "refresh_token").as_deref(),
+            Some("configured")
+        );
+        assert_eq!(requests[1].header("authorization"), Some("Bearer access-1"));
+        assert_eq!(requests[2].path, "/oauth/token");
+        assert_eq!(
+            form_value(&requests[2].body, "refresh_token").as_deref(),
+            Some("rotated-1")
+        );
+        assert_eq!(requests[3].header("authorization"), Some("Bearer access-2"));
+        assert_eq!(requests[4].header("authorization"), Some("Bearer access-2"));
+        assert_eq!(
+            super::read_cache("oauth2", &oauth2.token_url, "rotating").as_deref(),
+            Some("rotated-2")
+        );
+    }
+
+    #[tokio::test]
+    async fn oauth2_refresh_token_is_read_from_cache() {
+        let _ = cache_dir();
+        let server = test_server::start(vec![
+            (400, r#"{"error": "invalid_grant"}"#),
+            (
+                200,
+                r#"{"access_token": "access", "refresh_token": "rotated"}"#,
+            ),
+            (200, "{}"),
+        ])
+        .await;
+        let oauth2 = oauth2(&server, "cached");
+        super::write_cache("oauth2", &oauth2.token_url, "cached", "stale").unwrap();
+
+        let access = Access::OAuth2(oauth2.clone());
+        assert_eq!(get(&server, &access).await, reqwest::StatusCode::OK);
+
+        let requests = server.requests();
+        assert_eq!(
+            form_value(&requests[0].body, "refresh_token").as_deref(),
+            Some("stale")
+        );
+        assert_eq!(
+            form_value(&requests[1].body, "refresh_token").as_deref(),
+            Some("configured")
+        );
+        assert_eq!(requests[2].header("authorization"), Some("Bearer access"));
+        assert_eq!(
+            super::read_cache("oauth2", &oauth2.token_url, "cached").as_deref(),
+            Some("rotated")
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let path = super::cache_path("oauth2", &oauth2.token_url, "cached").unwrap();
+            let mode = std::fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_session_is_renewed() {
+        let _ = cache_dir();
+        let server = test_server::start(vec![
+            (401, r#"{"errorMessages": ["Unauthorized"]}"#),
+            (
+                200,
+                r#"{"session": {"name": "JSESSIONID", "value": "new"}}"#,
+            ),
+            (200, "{}"),
+        ])
+        .await;
+        super::write_cache("session", &server.url, "user", "JSESSIONID=stale").unwrap();
+
+        let access = Access::Session {
+            username: Username::Plain("user".to_owned()),
+            password: Secret::String("password".to_owned()),
+        };
+        assert_eq!(get(&server, &access).await, reqwest::StatusCode::OK);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].header("cookie"), Some("JSESSIONID=stale"));
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(requests[1].path, "/rest/auth/1/session");
+        let login: serde_json::Value = serde_json::from_str(&requests[1].body).unwrap();
+        assert_eq!(
+            login,
+            serde_json::json!({"username": "user", "password": "password"})
+        );
+        assert_eq!(requests[2].path, "/rest/api/2/myself");
+        assert_eq!(requests[2].header("cookie"), Some("JSESSIONID=new"));
+        assert_eq!(
+            super::read_cache("session", &server.url, "user").as_deref(),
+            Some("JSESSIONID=new")
+        );
+    }
 }
@@ -1,23 +1,44 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use anyhow::{format_err, Result};
+use anyhow::{bail, format_err, Context, Result};
 use serde::{Deserialize, Serialize};
 
 use crate::{confluence::ConfluenceServer, jira::JiraServer};
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct JiraQueryCustomField {
-    pub name: String,
-}
-
 #[derive(Serialize, Deserialize, Clone)]
 pub struct JiraQuery {
     pub jira: JiraServer,
     pub query: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Report(#[serde(with = "serde_yaml::with::singleton_map")] pub crate::report::Report);
+#[derive(Serialize, Clone)]
+pub struct Report {
+    /// Arbitrary labels used to select groups of reports, e.g. `weekly`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(flatten, with = "serde_yaml::with::singleton_map")]
+    pub report: crate::report::Report,
+}
+
+// `#[serde(flatten)]` buffers values and loses YAML tags like `!CustomField`, so the report is
+// deserialized from a YAML value instead
+impl<'de> Deserialize<'de> for Report {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let mut value = serde_yaml::Value::deserialize(deserializer)?;
+        let tags = match value.as_mapping_mut().and_then(|v| v.remove("tags")) {
+            None => Vec::new(),
+            Some(v) => serde_yaml::from_value(v).map_err(D::Error::custom)?,
+        };
+        let report =
+            serde_yaml::with::singleton_map::deserialize(value).map_err(D::Error::custom)?;
+        Ok(Self { tags, report })
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Config {
@@ -31,14 +52,712 @@ pub struct Config {
     pub reports: HashMap<String, Report>,
     #[serde(default)]
     pub substitutions: HashMap<String, String>,
+    /// Relations between issues of different Jira instances used by ad-hoc commands
+    #[serde(default)]
+    pub foreign_relations: Vec<crate::report::ForeignRelation>,
+    /// Timezone and formats of dates in reports, shared by all Jira instances
+    #[serde(default)]
+    pub dates: crate::jira::DateDisplay,
 }
 
 impl Config {
-    pub fn read(file: &str) -> Result<Self> {
-        let config = std::fs::read_to_string(file)
-            .map_err(|err| format_err!("Failed to load config file {:?}: {}", file, err))?;
-        let config: Self = serde_yaml::from_str(&config)
+    /// Jira instance from `jira_instances` or the default one when name is not provided
+    pub fn jira_instance(&self, name: Option<&str>) -> Result<&JiraServer> {
+        match name {
+            None => Ok(&self.default_jira_instance),
+            Some(name) => self.jira_instances.get(name).ok_or_else(|| {
+                format_err!("Jira instance {:?} is not defined in config file", name)
+            }),
+        }
+    }
+
+    /// JQL query with `%name%` substitutions replaced
+    pub fn substitute(&self, query: &str) -> String {
+        let mut query = query.replace('\n', " ").trim().to_string();
+        for (key, value) in &self.substitutions {
+            query = query.replace(&format!("%{key}%"), value);
+        }
+        query
+    }
+
+    /// Reads config file with its includes, then applies the profile and `UPRAVA_*` environment
+    /// variables on top of it. Custom fields set by display names are resolved on Jira instances
+    /// if `resolve_fields` is set
+    pub async fn read(file: &str, profile: Option<&str>, resolve_fields: bool) -> Result<Self> {
+        let mut value = read_value(std::path::Path::new(file), &mut Vec::new())?;
+
+        let profiles = match value.as_mapping_mut().and_then(|v| v.remove("profiles")) {
+            None => serde_yaml::Mapping::new(),
+            Some(serde_yaml::Value::Mapping(v)) => v,
+            Some(_) => bail!("Profiles in config file {:?} must be a mapping", file),
+        };
+        let overlay = match profile {
+            None => None,
+            Some(name) => match profiles.get(name) {
+                None => bail!("Profile {:?} is not defined in config file", name),
+                Some(overlay) => Some(overlay.clone()),
+            },
+        };
+        apply_overrides(&mut value, overlay, std::env::vars())?;
+
+        share_dates(&mut value)?;
+        translate_relations_map(&mut value)?;
+        if resolve_fields {
+            self::resolve_fields(&mut value).await?;
+        }
+
+        let config: Self = serde_yaml::from_value(value)
             .map_err(|err| format_err!("Failed to parse config file {:?}: {}", file, err))?;
+        config.dates.validate()?;
         Ok(config)
     }
 }
+
+/// Instances of one kind defined in config file
+struct Instances {
+    /// Instance kind for messages, e.g. `Jira`
+    kind: &'static str,
+    /// Section of named instances, e.g. `jira_instances`
+    section: &'static str,
+    named: serde_yaml::Mapping,
+}
+
+impl Instances {
+    fn of(config: &mut serde_yaml::Value, kind: &'static str, section: &'static str) -> Self {
+        let mut named = serde_yaml::Mapping::new();
+        if let Some(serde_yaml::Value::Mapping(instances)) = config.get_mut(section) {
+            for (name, instance) in instances.iter_mut() {
+                if let (Some(name), serde_yaml::Value::Mapping(instance)) =
+                    (name.as_str(), instance)
+                {
+                    instance.remove("name");
+                    named.insert(name.into(), serde_yaml::Value::Mapping(instance.clone()));
+                    instance.insert("name".into(), name.into());
+                }
+            }
+        }
+        Self {
+            kind,
+            section,
+            named,
+        }
+    }
+
+    /// Replaces name reference with the instance, names instances embedded with YAML anchors
+    fn resolve(&self, value: &mut serde_yaml::Value, path: &str) -> Result<()> {
+        match value {
+            serde_yaml::Value::String(name) => match self.named.get(name.as_str()) {
+                None => {
+                    let mut known: Vec<_> = self.named.keys().filter_map(|v| v.as_str()).collect();
+                    known.sort();
+                    bail!(
+                        "{} instance {:?} referenced at {} is not defined in {} (defined: {})",
+                        self.kind,
+                        name,
+                        path,
+                        self.section,
+                        known.join(", ")
+                    )
+                }
+                Some(instance) => {
+                    let mut instance = instance.clone();
+                    if let serde_yaml::Value::Mapping(v) = &mut instance {
+                        v.insert("name".into(), name.as_str().into());
+                    }
+                    *value = instance;
+                }
+            },
+            serde_yaml::Value::Mapping(instance) if !instance.contains_key("name") => {
+                let name = self
+                    .copy_of(instance)
+                    .or_else(|| instance.get("base_url").cloned())
+                    .ok_or_else(|| {
+                        format_err!(
+                            "{} instance at {} has neither base_url nor name of an instance \
+                             defined in {}",
+                            self.kind,
+                            path,
+                            self.section
+                        )
+                    })?;
+                instance.insert("name".into(), name);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns name of the named instance equal to the embedded one
+    fn copy_of(&self, instance: &serde_yaml::Mapping) -> Option<serde_yaml::Value> {
+        self.named
+            .iter()
+            .find(|(_, v)| v.as_mapping() == Some(instance))
+            .map(|(k, _)| k.clone())
+    }
+}
+
+/// Calls `f` for every value of fields named `field` in reports and relations with its path
+fn visit_references(
+    value: &mut serde_yaml::Value,
+    field: &str,
+    path: &str,
+    f: &mut dyn FnMut(&mut serde_yaml::Value, &str) -> Result<()>,
+) -> Result<()> {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                let key = key.as_str().unwrap_or_default();
+                let path = format!("{}.{}", path, key);
+                if key == field {
+                    f(value, &path)?;
+                } else {
+                    visit_references(value, field, &path, f)?;
+                }
+            }
+        }
+        serde_yaml::Value::Sequence(sequence) => {
+            for (i, value) in sequence.iter_mut().enumerate() {
+                visit_references(value, field, &format!("{}[{}]", path, i), f)?;
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => visit_references(&mut tagged.value, field, path, f)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Calls `f` for every instance of the kind: the default one, named ones and instances of
+/// reports and relations
+fn visit_instances(
+    config: &mut serde_yaml::Value,
+    field: &str,
+    f: &mut dyn FnMut(&mut serde_yaml::Value, &str) -> Result<()>,
+) -> Result<()> {
+    let default = format!("default_{}_instance", field);
+    if let Some(value) = config.get_mut(&default) {
+        f(value, &default)?;
+    }
+    let named = format!("{}_instances", field);
+    if let Some(serde_yaml::Value::Mapping(instances)) = config.get_mut(&named) {
+        for (name, value) in instances.iter_mut() {
+            f(
+                value,
+                &format!("{}.{}", named, name.as_str().unwrap_or_default()),
+            )?;
+        }
+    }
+    for section in ["reports", "foreign_relations"] {
+        if let Some(value) = config.get_mut(section) {
+            visit_references(value, field, section, f)?;
+        }
+    }
+    Ok(())
+}
+
+/// Resolves references like `jira: default` to instances from `jira_instances` and
+/// `confluence_instances`, and names all instances
+fn resolve_instances(config: &mut serde_yaml::Value) -> Result<()> {
+    for (kind, field, section) in [
+        ("Jira", "jira", "jira_instances"),
+        ("Confluence", "confluence", "confluence_instances"),
+    ] {
+        let instances = Instances::of(config, kind, section);
+        visit_instances(config, field, &mut |v, path| instances.resolve(v, path))?;
+    }
+    Ok(())
+}
+
+/// Applies the profile and `UPRAVA_*` variables, copies of named instances are found before
+/// and follow overrides of the named ones
+fn apply_overrides(
+    config: &mut serde_yaml::Value,
+    profile: Option<serde_yaml::Value>,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<()> {
+    name_copies(config)?;
+    let resolved = config.clone();
+    if let Some(overlay) = profile {
+        merge(config, overlay);
+    }
+    apply_env_overrides(config, vars)?;
+    sync_instances(config, &resolved)
+}
+
+/// Names instances embedded with YAML anchors, references and other instances are left to
+/// `resolve_instances` as overrides may define them
+fn name_copies(config: &mut serde_yaml::Value) -> Result<()> {
+    for (kind, field, section) in [
+        ("Jira", "jira", "jira_instances"),
+        ("Confluence", "confluence", "confluence_instances"),
+    ] {
+        let instances = Instances::of(config, kind, section);
+        visit_instances(config, field, &mut |value, _| {
+            if let serde_yaml::Value::Mapping(instance) = value {
+                if !instance.contains_key("name") {
+                    if let Some(name) = instances.copy_of(instance) {
+                        instance.insert("name".into(), name);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+/// Applies overrides of named instances to their copies: a copy made by a reference or a YAML
+/// anchor takes the overridden named instance with overrides of the copy itself on top. A copy
+/// having own overrides is a different instance, so it is named anew
+fn sync_instances(config: &mut serde_yaml::Value, resolved: &serde_yaml::Value) -> Result<()> {
+    let mut resolved = resolved.clone();
+    for (kind, field, section) in [
+        ("Jira", "jira", "jira_instances"),
+        ("Confluence", "confluence", "confluence_instances"),
+    ] {
+        let original = Instances::of(&mut resolved, kind, section);
+        let mut copies = HashMap::new();
+        visit_instances(&mut resolved, field, &mut |value, path| {
+            if let serde_yaml::Value::Mapping(instance) = value {
+                let mut instance = instance.clone();
+                if let Some(name) = instance.remove("name") {
+                    if original.named.get(&name).and_then(|v| v.as_mapping()) == Some(&instance) {
+                        copies.insert(path.to_owned(), (name, value.clone()));
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        let instances = Instances::of(config, kind, section);
+        let prefix = format!("{}.", section);
+        visit_instances(config, field, &mut |value, path| {
+            let (name, before) = match copies.get(path) {
+                Some(v) if !path.starts_with(&prefix) && value.is_mapping() => v,
+                _ => return Ok(()),
+            };
+            let mut instance = match instances.named.get(name) {
+                Some(v) => v.clone(),
+                None => return Ok(()),
+            };
+            match changes(before, value) {
+                None => {
+                    if let serde_yaml::Value::Mapping(v) = &mut instance {
+                        v.insert("name".into(), name.clone());
+                    }
+                }
+                Some(changes) => merge(&mut instance, changes),
+            }
+            *value = instance;
+            Ok(())
+        })?;
+    }
+    resolve_instances(config)
+}
+
+/// Returns keys of `after` differing from `before`, or the whole `after` if it is not a mapping
+fn changes(before: &serde_yaml::Value, after: &serde_yaml::Value) -> Option<serde_yaml::Value> {
+    if before == after {
+        return None;
+    }
+    match (before, after) {
+        (serde_yaml::Value::Mapping(before), serde_yaml::Value::Mapping(after)) => Some(
+            after
+                .iter()
+                .filter_map(|(key, value)| match before.get(key) {
+                    None => Some((key.clone(), value.clone())),
+                    Some(old) => changes(old, value).map(|v| (key.clone(), v)),
+                })
+                .collect::<serde_yaml::Mapping>()
+                .into(),
+        ),
+        _ => Some(after.clone()),
+    }
+}
+
+/// Copies the top-level `dates` to all Jira instances, so issues are shown the same way
+/// whichever instance they come from
+fn share_dates(config: &mut serde_yaml::Value) -> Result<()> {
+    let dates = match config.get("dates") {
+        None => return Ok(()),
+        Some(v) => v.clone(),
+    };
+    visit_instances(config, "jira", &mut |value, _| {
+        if let serde_yaml::Value::Mapping(v) = value {
+            v.insert("dates".into(), dates.clone());
+        }
+        Ok(())
+    })
+}
+
+/// Translates `relations_map` of old configs, pairs of custom and standard link names, into
+/// `relation_kinds`: custom names are added to relation kinds having the standard ones
+fn translate_relations_map(config: &mut serde_yaml::Value) -> Result<()> {
+    let mut warned = BTreeSet::new();
+    visit_instances(config, "jira", &mut |value, path| {
+        let instance = match value {
+            serde_yaml::Value::Mapping(v) => v,
+            _ => return Ok(()),
+        };
+        let map = match instance.remove("relations_map") {
+            None => return Ok(()),
+            Some(v) => v,
+        };
+        let map: Vec<(String, String)> = serde_yaml::from_value(map).map_err(|err| {
+            format_err!(
+                "Failed to parse relations_map at {}, use relation_kinds instead: {}",
+                path,
+                err
+            )
+        })?;
+        let mut kinds = match instance.get("relation_kinds") {
+            None => crate::report::default_relation_kinds(),
+            Some(v) => serde_yaml::from_value(v.clone()).map_err(|err| {
+                format_err!("Failed to parse relation_kinds at {}: {}", path, err)
+            })?,
+        };
+        for (name, equals_to) in map {
+            let links = kinds
+                .iter_mut()
+                .flat_map(|v| [&mut v.inward, &mut v.outward])
+                .find(|v| v.contains(&equals_to));
+            match links {
+                None => bail!(
+                    "relations_map at {} maps {:?} to {:?}, which is not a link name of \
+                     relation_kinds. relations_map is replaced by relation_kinds",
+                    path,
+                    name,
+                    equals_to
+                ),
+                Some(links) => links.push(name),
+            }
+        }
+        instance.insert("relation_kinds".into(), serde_yaml::to_value(&kinds)?);
+
+        let name = instance
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or(path);
+        if warned.insert(name.to_owned()) {
+            slog_scope::warn!(
+                "relations_map of Jira instance {:?} is deprecated, use relation_kinds",
+                name
+            );
+        }
+        Ok(())
+    })
+}
+
+/// Replaces display names of custom fields and aliases with IDs from the fields lists of Jira
+/// instances. Instances with IDs only aren't queried
+async fn resolve_fields(config: &mut serde_yaml::Value) -> Result<()> {
+    let mut unresolved = BTreeMap::new();
+    visit_instances(config, "jira", &mut |value, path| {
+        let jira: JiraServer = serde_yaml::from_value(value.clone())
+            .map_err(|err| format_err!("Failed to parse Jira instance at {}: {}", path, err))?;
+        if !jira.is_fields_resolved() {
+            unresolved.entry(jira.name.clone()).or_insert(jira);
+        }
+        Ok(())
+    })?;
+
+    let mut resolved = BTreeMap::new();
+    for (name, mut jira) in unresolved {
+        let fields = jira.fields().await.with_context(|| {
+            format!(
+                "Failed to resolve custom fields of Jira instance {:?}",
+                name
+            )
+        })?;
+        jira.resolve_fields(&fields)?;
+        let custom_fields = serde_yaml::to_value(&jira.custom_fields)?;
+        let field_aliases = serde_yaml::to_value(&jira.field_aliases)?;
+        resolved.insert(name, (custom_fields, field_aliases));
+    }
+    if resolved.is_empty() {
+        return Ok(());
+    }
+
+    visit_instances(config, "jira", &mut |value, _| {
+        let name = value.get("name").and_then(|v| v.as_str());
+        if let Some((custom_fields, field_aliases)) = name.and_then(|v| resolved.get(v)) {
+            if let serde_yaml::Value::Mapping(v) = value {
+                v.insert("custom_fields".into(), custom_fields.clone());
+                v.insert("fields".into(), field_aliases.clone());
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Environment variables which aren't config overrides
+const ENV_RESERVED: [&str; 2] = ["UPRAVA_CONFIG", "UPRAVA_PROFILE"];
+
+/// Parses YAML file and merges files from its `include` list under it. Included paths are
+/// relative to the including file
+fn read_value(
+    file: &std::path::Path,
+    stack: &mut Vec<std::path::PathBuf>,
+) -> Result<serde_yaml::Value> {
+    let path = std::fs::canonicalize(file)
+        .map_err(|err| format_err!("Failed to load config file {:?}: {}", file, err))?;
+    if stack.contains(&path) {
+        bail!("Config file {:?} includes itself", file)
+    }
+
+    let content = std::fs::read_to_string(&path)
+        .map_err(|err| format_err!("Failed to load config file {:?}: {}", file, err))?;
+    let mut value: serde_yaml::Value = serde_yaml::from_str(&content)
+        .map_err(|err| format_err!("Failed to parse config file {:?}: {}", file, err))?;
+    if value.is_null() {
+        value = serde_yaml::Value::Mapping(Default::default());
+    }
+
+    let includes = match value.as_mapping_mut().and_then(|v| v.remove("include")) {
+        None => Vec::new(),
+        Some(serde_yaml::Value::String(v)) => vec![v],
+        Some(v) => serde_yaml::from_value(v)
+            .map_err(|err| format_err!("Failed to parse include list of {:?}: {}", file, err))?,
+    };
+
+    stack.push(path.clone());
+    let dir = path.parent().unwrap_or(std::path::Path::new("/"));
+    let mut result = serde_yaml::Value::Mapping(Default::default());
+    for include in includes {
+        let include = dir.join(shellexpand::tilde(&include).as_ref());
+        let included = read_value(&include, stack)?;
+        merge(&mut result, included);
+    }
+    stack.pop();
+
+    merge(&mut result, value);
+    Ok(result)
+}
+
+/// Merges mappings recursively, other values of `overlay` replace values of `base`
+fn merge(base: &mut serde_yaml::Value, overlay: serde_yaml::Value) {
+    match (base, overlay) {
+        (serde_yaml::Value::Mapping(base), serde_yaml::Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(current) => merge(current, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Applies `UPRAVA_*` variables on top of config. Name after the prefix is a path in config
+/// separated by `__`, e.g. `UPRAVA_JIRA_INSTANCES__DEFAULT__BASE_URL`. Keys are matched
+/// case-insensitively, missing keys are added in lowercase. Value replacing a string is taken as
+/// is, other values are parsed as YAML, so quotes make a string of a missing value
+fn apply_env_overrides(
+    config: &mut serde_yaml::Value,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<()> {
+    let mut vars: Vec<_> = vars
+        .filter(|(name, _)| name.starts_with("UPRAVA_") && !ENV_RESERVED.contains(&name.as_str()))
+        .collect();
+    vars.sort();
+
+    for (name, value) in vars {
+        let path: Vec<_> = name["UPRAVA_".len()..].split("__").collect();
+        if path.iter().any(|v| v.is_empty()) {
+            bail!("Invalid config override variable {}", name)
+        }
+        slog_scope::debug!("Config is overridden by {}", name);
+
+        let mut target = &mut *config;
+        for key in path {
+            if target.is_null() {
+                *target = serde_yaml::Value::Mapping(Default::default());
+            }
+            let mapping = match target {
+                serde_yaml::Value::Mapping(v) => v,
+                _ => bail!(
+                    "Variable {} overrides key {:?} of a value which is not a mapping",
+                    name,
+                    key
+                ),
+            };
+            let mut matching = mapping.keys().filter(|k| {
+                k.as_str()
+                    .map(|k| k.eq_ignore_ascii_case(key))
+                    .unwrap_or_default()
+            });
+            let existing = matching.next().cloned();
+            if matching.next().is_some() {
+                bail!("Variable {} matches several keys {:?} of config", name, key)
+            }
+            let key = existing.unwrap_or_else(|| serde_yaml::Value::String(key.to_lowercase()));
+            target = mapping.entry(key).or_insert(serde_yaml::Value::Null);
+        }
+
+        let overlay = if target.is_string() {
+            serde_yaml::Value::String(value)
+        } else {
+            serde_yaml::from_str(&value)
+                .map_err(|err| format_err!("Failed to parse variable {}: {}", name, err))?
+        };
+        merge(target, overlay);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    fn yaml(s: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn relations_map_is_translated_to_relation_kinds() {
+        let mut config = yaml(
+            "default_jira_instance:\n  name: j\n  relations_map:\n    - [Ожидает, depends on]\n",
+        );
+        super::translate_relations_map(&mut config).unwrap();
+        let instance = &config["default_jira_instance"];
+        assert!(instance.get("relations_map").is_none());
+        let kinds: Vec<crate::report::RelationKind> =
+            serde_yaml::from_value(instance["relation_kinds"].clone()).unwrap();
+        let dependance = kinds.iter().find(|v| v.name == "Dependance").unwrap();
+        assert_eq!(dependance.inward, vec!["depends on", "Ожидает"]);
+    }
+
+    #[test]
+    fn relations_map_with_unknown_link_fails() {
+        let mut config =
+            yaml("default_jira_instance:\n  relations_map:\n    - [Ожидает, waits for]\n");
+        let err = super::translate_relations_map(&mut config).unwrap_err();
+        assert!(err.to_string().contains("relation_kinds"));
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn merge_mappings_recursively() {
+        let mut base = yaml("a: {b: 1, c: [1, 2]}\nd: x\n");
+        super::merge(&mut base, yaml("a: {c: [3], e: 2}\nd: {f: 1}\n"));
+        assert_eq!(base, yaml("a: {b: 1, c: [3], e: 2}\nd: {f: 1}\n"));
+    }
+
+    #[test]
+    fn env_override_keeps_string_type() {
+        let mut config = yaml("default_jira_instance:\n  access:\n    Token:\n      String: old\n");
+        super::apply_env_overrides(
+            &mut config,
+            vars(&[(
+                "UPRAVA_DEFAULT_JIRA_INSTANCE__ACCESS__TOKEN__STRING",
+                "12345",
+            )]),
+        )
+        .unwrap();
+        assert_eq!(
+            config["default_jira_instance"]["access"]["Token"]["String"],
+            serde_yaml::Value::String("12345".to_owned())
+        );
+    }
+
+    #[test]
+    fn env_override_parses_missing_values() {
+        let mut config = yaml("default_jira_instance:\n  http:\nreports:\n  MyReport: {}\n");
+        super::apply_env_overrides(
+            &mut config,
+            vars(&[
+                ("UPRAVA_DEFAULT_JIRA_INSTANCE__HTTP__RETRIES", "0"),
+                (
+                    "UPRAVA_DEFAULT_JIRA_INSTANCE__HTTP__USER_AGENT",
+                    "\"12345\"",
+                ),
+                ("UPRAVA_REPORTS__MYREPORT__TITLE", "yes"),
+                ("UPRAVA_CONFIG", "ignored"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            yaml(
+                "default_jira_instance:\n  http: {retries: 0, user_agent: '12345'}\n\
+                 reports:\n  MyReport: {title: 'yes'}\n"
+            )
+        );
+    }
+
+    #[test]
+    fn env_override_of_scalar_fails() {
+        let mut config = yaml("default_jira_instance: x\n");
+        assert!(super::apply_env_overrides(
+            &mut config,
+            vars(&[("UPRAVA_DEFAULT_JIRA_INSTANCE__BASE_URL", "https://x")]),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn embedded_instance_without_base_url_fails() {
+        let mut config = yaml("default_jira_instance:\n  access: {Token: {String: x}}\n");
+        let err = super::resolve_instances(&mut config).unwrap_err();
+        assert!(err.to_string().contains("base_url"), "{}", err);
+    }
+
+    #[test]
+    fn embedded_instance_is_named_by_base_url() {
+        let mut config = yaml(
+            "jira_instances:\n  main: {base_url: https://a.test/}\n\
+             default_jira_instance: {base_url: https://a.test/}\n\
+             reports:\n  r:\n    jira: {base_url: https://b.test/}\n",
+        );
+        super::resolve_instances(&mut config).unwrap();
+        assert_eq!(config["default_jira_instance"]["name"], yaml("main"));
+        assert_eq!(
+            config["reports"]["r"]["jira"]["name"],
+            yaml("https://b.test/")
+        );
+    }
+
+    #[test]
+    fn anchored_instance_follows_env_overrides() {
+        let mut config = yaml(
+            "jira_instances:\n  main: &main\n    base_url: https://a.test/\n    \
+             access: {Token: {String: old}}\n\
+             reports:\n  r:\n    jira: *main\n  s:\n    jira: *main\n",
+        );
+        super::apply_overrides(
+            &mut config,
+            None,
+            vars(&[
+                ("UPRAVA_JIRA_INSTANCES__MAIN__ACCESS__TOKEN__STRING", "new"),
+                ("UPRAVA_REPORTS__S__JIRA__BASE_URL", "https://b.test/"),
+            ]),
+        )
+        .unwrap();
+        let copy = &config["reports"]["r"]["jira"];
+        assert_eq!(copy["name"], yaml("main"));
+        assert_eq!(copy["access"]["Token"]["String"], yaml("new"));
+        let changed = &config["reports"]["s"]["jira"];
+        assert_eq!(changed["name"], yaml("https://b.test/"));
+        assert_eq!(changed["access"]["Token"]["String"], yaml("new"));
+    }
+
+    #[test]
+    fn profile_defines_referenced_instance() {
+        let mut config = yaml("default_jira_instance: stage\n");
+        super::apply_overrides(
+            &mut config,
+            Some(yaml(
+                "jira_instances:\n  stage: {base_url: https://s.test/}\n",
+            )),
+            vars(&[]),
+        )
+        .unwrap();
+        assert_eq!(config["default_jira_instance"]["name"], yaml("stage"));
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{confluence::ConfluenceServer, jira::JiraServer};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CheckStatus {
+    Ok,
+    /// Suspicious, but doesn't break reports
+    Warning,
+    Failed,
+}
+
+impl std::fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Warning => "WARN",
+            CheckStatus::Failed => "FAIL",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+pub struct CheckResult {
+    /// Kind of the check, e.g. `auth`
+    pub check: &'static str,
+    /// Checked instance, field, query or page
+    pub subject: String,
+    pub status: CheckStatus,
+    pub details: String,
+}
+
+/// Results of config file validation, optionally against live servers
+#[derive(Default)]
+pub struct ConfigCheck {
+    pub results: Vec<CheckResult>,
+}
+
+/// Names of `%name%` substitutions used in the query
+fn placeholders(query: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut rest = query;
+    while let Some(start) = rest.find('%') {
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('%') else {
+            break;
+        };
+        let name = &after[..end];
+        if !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        {
+            result.push(name);
+            rest = &after[end + 1..];
+        } else {
+            rest = &after[end..];
+        }
+    }
+    result
+}
+
+/// Field types which values of the mapped custom field are parsed from
+fn expected_field_types(field: &str) -> &'static [&'static str] {
+    match field {
+        "epic_link" | "parent_link" => &["any", "string"],
+        "planned_start" | "planned_end" => &["date", "datetime"],
+        _ => &["string"],
+    }
+}
+
+/// Field types matching the type of field alias, aliases without type match any field
+fn alias_field_types(kind: crate::jira::FieldType) -> &'static [&'static str] {
+    match kind {
+        crate::jira::FieldType::String => &["string", "any"],
+        crate::jira::FieldType::Number => &["number"],
+        crate::jira::FieldType::Date | crate::jira::FieldType::DateTime => &["date", "datetime"],
+        crate::jira::FieldType::User => &["user"],
+        crate::jira::FieldType::Option => &["option", "priority", "status", "resolution"],
+        crate::jira::FieldType::Cascading => &["option-with-child"],
+        crate::jira::FieldType::Version => &["version"],
+        crate::jira::FieldType::Array => &["array"],
+    }
+}
+
+impl ConfigCheck {
+    pub async fn run(config: &crate::config::Config, offline: bool) -> Self {
+        let mut check = Self::default();
+        check.check_structure(config);
+        if !offline {
+            let jira_failed = check.check_jira_instances(config).await;
+            check.check_queries(config, &jira_failed).await;
+            let confluence_failed = check.check_confluence_instances(config).await;
+            check.check_pages(config, &confluence_failed).await;
+        }
+        check
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|v| v.status == CheckStatus::Failed)
+            .count()
+    }
+
+    fn add(
+        &mut self,
+        check: &'static str,
+        subject: impl Into<String>,
+        status: CheckStatus,
+        details: impl Into<String>,
+    ) {
+        self.results.push(CheckResult {
+            check,
+            subject: subject.into(),
+            status,
+            details: details.into(),
+        })
+    }
+
+    fn reports(config: &crate::config::Config) -> Vec<(&String, &crate::report::Report)> {
+        let mut reports: Vec<_> = config
+            .reports
+            .iter()
+            .map(|(name, v)| (name, &v.report))
+            .collect();
+        reports.sort_by_key(|(name, _)| *name);
+        reports
+    }
+
+    /// All Jira instances by name: default, named and embedded into reports
+    fn jira_instances(config: &crate::config::Config) -> BTreeMap<&str, &JiraServer> {
+        let mut result = BTreeMap::new();
+        result.insert(
+            config.default_jira_instance.name.as_str(),
+            &config.default_jira_instance,
+        );
+        for v in config.jira_instances.values() {
+            result.insert(v.name.as_str(), v);
+        }
+        for (_, report) in Self::reports(config) {
+            for query in report.queries() {
+                result
+                    .entry(query.jira.name.as_str())
+                    .or_insert(&query.jira);
+            }
+        }
+        result
+    }
+
+    /// All Confluence instances by name: default, named and embedded into reports
+    fn confluence_instances(config: &crate::config::Config) -> BTreeMap<&str, &ConfluenceServer> {
+        let mut result = BTreeMap::new();
+        result.insert(
+            config.default_confluence_instance.name.as_str(),
+            &config.default_confluence_instance,
+        );
+        for v in config.confluence_instances.values() {
+            result.insert(v.name.as_str(), v);
+        }
+        for (_, report) in Self::reports(config) {
+            let page = report.page();
+            result
+                .entry(page.confluence.name.as_str())
+                .or_insert(page.confluence);
+        }
+        result
+    }
+
+    fn check_structure(&mut self, config: &crate::config::Config) {
+        let found = self.results.len();
+        let mut used_jira = BTreeSet::new();
+        let mut used_confluence = BTreeSet::new();
+        used_jira.insert(config.default_jira_instance.name.as_str());
+        used_confluence.insert(config.default_confluence_instance.name.as_str());
+        let relations = config.foreign_relations.iter().chain(
+            config
+                .reports
+                .values()
+                .flat_map(|v| v.report.foreign_relations()),
+        );
+        for relation in relations {
+            used_jira.insert(relation.from.jira.name.as_str());
+            used_jira.insert(relation.to.jira.name.as_str());
+        }
+
+        for (name, report) in Self::reports(config) {
+            used_confluence.insert(report.page().confluence.name.as_str());
+            for query in report.queries() {
+                used_jira.insert(query.jira.name.as_str());
+                for placeholder in placeholders(&query.query) {
+                    if !config.substitutions.contains_key(placeholder) {
+                        self.add(
+                            "substitution",
+                            format!("report {}", name),
+                            CheckStatus::Failed,
+                            format!("%{}% is not defined in substitutions", placeholder),
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut jira_names: Vec<_> = config.jira_instances.keys().collect();
+        jira_names.sort();
+        for name in jira_names {
+            if !used_jira.contains(name.as_str()) {
+                self.add(
+                    "instance",
+                    format!("jira {}", name),
+                    CheckStatus::Warning,
+                    "not used by reports, relations or as the default instance",
+                );
+            }
+        }
+        let mut confluence_names: Vec<_> = config.confluence_instances.keys().collect();
+        confluence_names.sort();
+        for name in confluence_names {
+            if !used_confluence.contains(name.as_str()) {
+                self.add(
+                    "instance",
+                    format!("confluence {}", name),
+                    CheckStatus::Warning,
+                    "not used by reports or as the default instance",
+                );
+            }
+        }
+
+        if self.results.len() == found {
+            self.add(
+                "structure",
+                "config",
+                CheckStatus::Ok,
+                format!("{} reports", config.reports.len()),
+            );
+        }
+    }
+
+    /// Checks access and custom fields set by IDs or display names, returns names of unavailable
+    /// instances
+    async fn check_jira_instances(&mut self, config: &crate::config::Config) -> BTreeSet<String> {
+        let mut failed = BTreeSet::new();
+        for (name, jira) in Self::jira_instances(config) {
+            let subject = format!("jira {}", name);
+            match jira.myself().await {
+                Err(err) => {
+                    self.add("auth", &subject, CheckStatus::Failed, format!("{:#}", err));
+                    failed.insert(name.to_owned());
+                    continue;
+                }
+                Ok(user) => {
+                    let user = user.display_name.or(user.name).unwrap_or_default();
+                    self.add(
+                        "auth",
+                        &subject,
+                        CheckStatus::Ok,
+                        format!("logged in as {}", user),
+                    );
+                }
+            }
+
+            let fields = match jira.fields().await {
+                Err(err) => {
+                    self.add(
+                        "fields",
+                        &subject,
+                        CheckStatus::Failed,
+                        format!("{:#}", err),
+                    );
+                    continue;
+                }
+                Ok(v) => v,
+            };
+            let mapped = jira
+                .custom_fields
+                .fields()
+                .into_iter()
+                .map(|(field, v)| (field, v, expected_field_types(field)));
+            let aliases = jira.field_aliases.iter().map(|(field, v)| {
+                let expected = v.kind.map_or(&[][..], alias_field_types);
+                (field.as_str(), &v.field, expected)
+            });
+            for (field, custom_field, expected) in mapped.chain(aliases) {
+                let subject = format!("jira {} {}", name, field);
+                let mut custom_field = custom_field.clone();
+                if let Err(err) = custom_field.resolve(name, &fields) {
+                    self.add("field", subject, CheckStatus::Failed, format!("{:#}", err));
+                    continue;
+                }
+                let details = fields
+                    .iter()
+                    .find(|v| v.id.as_deref() == Some(custom_field.name.as_str()));
+                match details {
+                    None if custom_field.name.starts_with("customfield_") => self.add(
+                        "field",
+                        subject,
+                        CheckStatus::Failed,
+                        format!("{} doesn't exist", custom_field.name),
+                    ),
+                    None => self.add(
+                        "field",
+                        subject,
+                        CheckStatus::Ok,
+                        format!("{} (built-in)", custom_field.name),
+                    ),
+                    Some(details) => {
+                        let display_name = details.name.clone().unwrap_or_default();
+                        match &details.schema {
+                            None => self.add(
+                                "field",
+                                subject,
+                                CheckStatus::Warning,
+                                format!("{} ({}) has no type", custom_field.name, display_name),
+                            ),
+                            Some(schema)
+                                if !expected.is_empty()
+                                    && !expected.contains(&schema.type_.as_str()) =>
+                            {
+                                self.add(
+                                    "field",
+                                    subject,
+                                    CheckStatus::Failed,
+                                    format!(
+                                        "{} ({}) has type {}, expected {}",
+                                        custom_field.name,
+                                        display_name,
+                                        schema.type_,
+                                        expected.join(" or ")
+                                    ),
+                                )
+                            }
+                            Some(schema) => self.add(
+                                "field",
+                                subject,
+                                CheckStatus::Ok,
+                                format!(
+                                    "{} ({}, {})",
+                                    custom_field.name, display_name, schema.type_
+                                ),
+                            ),
+                        }
+                    }
+                }
+            }
+        }
+        failed
+    }
+
+    async fn check_queries(&mut self, config: &crate::config::Config, failed: &BTreeSet<String>) {
+        for (name, report) in Self::reports(config) {
+            for query in report.queries() {
+                let subject = format!("report {} on jira {}", name, query.jira.name);
+                if failed.contains(&query.jira.name) {
+                    self.add(
+                        "query",
+                        subject,
+                        CheckStatus::Failed,
+                        "Jira instance is unavailable",
+                    );
+                    continue;
+                }
+                let jql = config.substitute(&query.query);
+                let params = crate::jira::SearchGetParams {
+                    max_results: Some(0),
+                    validate_query: Some(true),
+                    fields: Some(vec!["key".to_owned()]),
+                    ..crate::jira::SearchGetParams::new(&jql)
+                };
+                match query.jira.search(&params).await {
+                    Err(err) => {
+                        self.add("query", subject, CheckStatus::Failed, format!("{:#}", err))
+                    }
+                    Ok(_) => self.add("query", subject, CheckStatus::Ok, jql),
+                }
+            }
+        }
+    }
+
+    /// Checks access, returns names of unavailable instances
+    async fn check_confluence_instances(
+        &mut self,
+        config: &crate::config::Config,
+    ) -> BTreeSet<String> {
+        let mut failed = BTreeSet::new();
+        for (name, confluence) in Self::confluence_instances(config) {
+            let subject = format!("confluence {}", name);
+            match confluence.current_user().await {
+                Err(err) => {
+                    self.add("auth", subject, CheckStatus::Failed, format!("{:#}", err));
+                    failed.insert(name.to_owned());
+                }
+                Ok(user) => {
+                    let user = user
+                        .get("displayName")
+                        .or_else(|| user.get("username"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_owned();
+                    self.add(
+                        "auth",
+                        subject,
+                        CheckStatus::Ok,
+                        format!("logged in as {}", user),
+                    );
+                }
+            }
+        }
+        failed
+    }
+
+    async fn check_pages(&mut self, config: &crate::config::Config, failed: &BTreeSet<String>) {
+        let mut spaces = BTreeMap::new();
+        for (name, report) in Self::reports(config) {
+            let page = report.page();
+            let subject = format!("report {}", name);
+            if failed.contains(&page.confluence.name) {
+                self.add(
+                    "page",
+                    subject,
+                    CheckStatus::Failed,
+                    "Confluence instance is unavailable",
+                );
+                continue;
+            }
+
+            let space_key = (page.confluence.name.clone(), page.space.to_owned());
+            if !spaces.contains_key(&space_key) {
+                let result = page.confluence.get_space(page.space).await;
+                let subject = format!("confluence {} space {}", page.confluence.name, page.space);
+                match &result {
+                    Err(err) => {
+                        self.add("space", subject, CheckStatus::Failed, format!("{:#}", err))
+                    }
+                    Ok(space) => {
+                        let space_name = space
+                            .get("name")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default();
+                        self.add("space", subject, CheckStatus::Ok, space_name)
+                    }
+                }
+                spaces.insert(space_key.clone(), result.is_ok());
+            }
+            if spaces.get(&space_key) == Some(&false) {
+                self.add(
+                    "page",
+                    subject,
+                    CheckStatus::Failed,
+                    format!("space {} is unavailable", page.space),
+                );
+                continue;
+            }
+
+            match page.confluence.get_page(page.space, page.title).await {
+                Err(err) => self.add("page", subject, CheckStatus::Failed, format!("{:#}", err)),
+                Ok(_) => self.add(
+                    "page",
+                    subject,
+                    CheckStatus::Ok,
+                    format!("{}/{}", page.space, page.title),
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{placeholders, CheckStatus, ConfigCheck};
+
+    #[test]
+    fn placeholders_of_query() {
+        assert_eq!(
+            placeholders("project = %project% AND sprint = %sprint-1%"),
+            vec!["project", "sprint-1"]
+        );
+        assert_eq!(placeholders("%a%%b%"), vec!["a", "b"]);
+        assert_eq!(
+            placeholders("summary ~ '100% done' AND x = %x"),
+            Vec::<&str>::new()
+        );
+        assert_eq!(placeholders("summary ~ '5% of %team%'"), vec!["team"]);
+        assert_eq!(placeholders("summary ~ '% off %' or %y%"), vec!["y"]);
+    }
+
+    async fn config(yaml: &str) -> crate::config::Config {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("uprava.yaml");
+        std::fs::write(&file, yaml).unwrap();
+        crate::config::Config::read(file.to_str().unwrap(), None, false)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn structure_check() {
+        let config = config(
+            "jira_instances:\n  main: &main {base_url: https://jira.test/, access: &access \
+             {Token: {String: x}}}\n  spare: {base_url: https://spare.test/, access: *access}\n\
+             default_jira_instance: *main\n\
+             default_confluence_instance: &wiki {base_url: https://wiki.test/, access: *access}\n\
+             substitutions:\n  project: T\n\
+             reports:\n  r:\n    ConfluenceRoadmap:\n      confluence: *wiki\n      space: DOCS\n      title: R\n      \
+             query_set:\n        - jira: main\n          query: project = %project% AND team = %team%\n",
+        )
+        .await;
+        let mut check = ConfigCheck::default();
+        check.check_structure(&config);
+        let results: Vec<_> = check
+            .results
+            .iter()
+            .map(|v| (v.check, v.subject.as_str(), v.status, v.details.as_str()))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                (
+                    "substitution",
+                    "report r",
+                    CheckStatus::Failed,
+                    "%team% is not defined in substitutions"
+                ),
+                (
+                    "instance",
+                    "jira spare",
+                    CheckStatus::Warning,
+                    "not used by reports, relations or as the default instance"
+                ),
+            ]
+        );
+        assert_eq!(check.failed(), 1);
+    }
+}
@@ -1,64 +1,53 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ConfluenceServer {
+    /// Name of the instance in config file, or base URL for unnamed ones. Set when config loads
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
     pub base_url: url::Url,
     #[serde(with = "serde_yaml::with::singleton_map")]
     pub access: crate::authentication::Access,
+    #[serde(default)]
+    pub http: crate::http::HttpConfig,
 }
 
-impl ConfluenceServer {
-    pub fn http_client(&self) -> Result<reqwest::Client> {
-        let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert(
-            reqwest::header::ACCEPT,
-            reqwest::header::HeaderValue::from_static("application/json"),
-        );
-        headers.insert(
-            reqwest::header::CONTENT_TYPE,
-            reqwest::header::HeaderValue::from_static("application/json"),
-        );
-        match &self.access {
-            crate::authentication::Access::Token(secret) => {
-                let mut secret = reqwest::header::HeaderValue::from_str(&format!(
-                    "Bearer {}",
-                    secret.get()?.trim()
-                ))?;
-                secret.set_sensitive(true);
-                let _ = headers.insert(reqwest::header::AUTHORIZATION, secret);
-            }
-            crate::authentication::Access::JSessionID(secret) => {
-                let mut secret = reqwest::header::HeaderValue::from_str(&format!(
-                    "JSESSIONID={}",
-                    secret.get()?.trim()
-                ))?;
-                secret.set_sensitive(true);
-                let _ = headers.insert(reqwest::header::COOKIE, secret);
-            }
-        }
+// Instances are identified by name, so copies with different secrets or settings are the same
+impl PartialEq for ConfluenceServer {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
 
-        let http_client = reqwest::ClientBuilder::new()
-            .timeout(std::time::Duration::from_secs(30))
-            .default_headers(headers)
-            .build()?;
+impl Eq for ConfluenceServer {}
+
+impl std::hash::Hash for ConfluenceServer {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
+}
 
-        Ok(http_client)
+impl ConfluenceServer {
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        self.http.client()
     }
 
     pub async fn http_get(&self, path: &str, params: &[(&str, &str)]) -> Result<String> {
         let http_client = self.http_client()?;
 
-        let mut url = self.base_url.clone();
-        url.set_path(path);
+        let mut url = crate::http::api_url(&self.base_url, path);
         url.query_pairs_mut().extend_pairs(params);
 
-        let response = http_client
-            .get(url.clone())
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
+        let response = self
+            .http
+            .send(
+                &self.base_url,
+                &self.access,
+                http_client
+                    .get(url.clone())
+                    .header(reqwest::header::ACCEPT, "application/json"),
+            )
             .await?;
 
         Ok(response)
@@ -72,22 +61,38 @@ impl ConfluenceServer {
     ) -> Result<String> {
         let http_client = self.http_client()?;
 
-        let mut url = self.base_url.clone();
-        url.set_path(path);
+        let mut url = crate::http::api_url(&self.base_url, path);
         url.query_pairs_mut().extend_pairs(params);
 
-        let response = http_client
-            .put(url.clone())
-            .body(body)
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
+        let response = self
+            .http
+            .send(
+                &self.base_url,
+                &self.access,
+                http_client
+                    .put(url.clone())
+                    .header(reqwest::header::ACCEPT, "application/json")
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body),
+            )
             .await?;
 
         Ok(response)
     }
 
+    /// User the instance is accessed as
+    pub async fn current_user(&self) -> Result<serde_json::Value> {
+        let response = self.http_get("/rest/api/user/current", &[]).await?;
+        Ok(serde_json::de::from_str(&response)?)
+    }
+
+    pub async fn get_space(&self, space: &str) -> Result<serde_json::Value> {
+        let response = self
+            .http_get(&format!("/rest/api/space/{}", space), &[])
+            .await?;
+        Ok(serde_json::de::from_str(&response)?)
+    }
+
     pub async fn get_content(
         &self,
         space: &str,
@@ -118,6 +123,28 @@ impl ConfluenceServer {
         Ok(json)
     }
 
+    /// The page with the title, fails if it doesn't exist
+    pub async fn get_page(
+        &self,
+        space: &str,
+        title: &str,
+    ) -> Result<crate::confluence_content_get::GetResult> {
+        match self
+            .get_content(space, title)
+            .await?
+            .results
+            .into_iter()
+            .next()
+        {
+            None => Err(crate::error::Error::new(
+                crate::error::ErrorKind::NotFound,
+                format!("Page {:?} not found in space {:?}", title, space),
+            )
+            .into()),
+            Some(v) => Ok(v),
+        }
+    }
+
     pub async fn update_content(
         &self,
         content_id: u64,
@@ -168,24 +195,26 @@ impl ConfluenceServer {
 
         let http_client = self.http_client()?;
 
-        let mut url = self.base_url.clone();
-        url.set_path(&format!(
-            "/rest/api/content/{}/child/attachment",
-            content_id
-        ));
+        let mut url = crate::http::api_url(
+            &self.base_url,
+            &format!("/rest/api/content/{}/child/attachment", content_id),
+        );
         url.query_pairs_mut()
             .extend_pairs(&[("allowDuplicated", "true")]);
 
         let form = multipart::Form::new().part("file", Self::file_part(file_path, filename).await?);
 
-        let response = http_client
-            .post(url)
-            .header("X-Atlassian-Token", "nocheck")
-            .multipart(form)
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
+        let response = self
+            .http
+            .send(
+                &self.base_url,
+                &self.access,
+                http_client
+                    .post(url)
+                    .header(reqwest::header::ACCEPT, "application/json")
+                    .header("X-Atlassian-Token", "nocheck")
+                    .multipart(form),
+            )
             .await?;
 
         Ok(response)
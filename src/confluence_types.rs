@@ -25,6 +25,7 @@ pub struct ContentBody {
 }
 
 #[derive(Deserialize, Debug)]
+#[allow(dead_code)]
 #[serde(bound = "for<'de2> DATA: Deserialize<'de2>")]
 pub struct PagedResult<DATA> {
     pub results: Vec<DATA>,
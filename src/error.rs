@@ -0,0 +1,280 @@
+use serde::Deserialize;
+
+/// Class of failure, defines the exit code of the application
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Other,
+    /// Missing or rejected credentials
+    Auth,
+    NotFound,
+    /// Invalid request, query or configuration
+    Validation,
+    /// Connection failures, timeouts and unavailable servers
+    Network,
+}
+
+impl ErrorKind {
+    /// Exit code 2 is used by clap for invalid arguments
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Auth => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::Validation => 5,
+            ErrorKind::Network => 6,
+        }
+    }
+
+    fn of_status(status: reqwest::StatusCode) -> Self {
+        match status.as_u16() {
+            401 | 403 => ErrorKind::Auth,
+            404 => ErrorKind::NotFound,
+            400 | 409 | 413 | 422 => ErrorKind::Validation,
+            429 | 502..=504 => ErrorKind::Network,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// Kind of the first typed error in the chain
+    pub fn of_error(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(v) = cause.downcast_ref::<Error>() {
+                return v.kind;
+            }
+            if let Some(v) = cause.downcast_ref::<ApiError>() {
+                return v.kind;
+            }
+            if let Some(v) = cause.downcast_ref::<reqwest::Error>() {
+                if let Some(status) = v.status() {
+                    return Self::of_status(status);
+                }
+                if v.is_timeout() || v.is_connect() || v.is_request() {
+                    return ErrorKind::Network;
+                }
+            }
+        }
+        ErrorKind::Other
+    }
+}
+
+/// Failure detected by the application itself, e.g. missing Confluence page
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Error payload of Jira and Confluence REST APIs. Jira sets `errorMessages` and `errors` by
+/// field, Confluence sets `message`, OAuth 2.0 servers set `error_description`
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ErrorPayload {
+    #[serde(default)]
+    error_messages: Vec<String>,
+    #[serde(default)]
+    errors: Option<serde_json::Value>,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default, rename = "error_description")]
+    error_description: Option<String>,
+}
+
+impl ErrorPayload {
+    fn messages(self) -> Vec<String> {
+        let mut messages = self.error_messages;
+        if let Some(serde_json::Value::Object(errors)) = self.errors {
+            for (field, error) in errors {
+                let error = match error {
+                    serde_json::Value::String(v) => v,
+                    v => v.to_string(),
+                };
+                messages.push(format!("{}: {}", field, error))
+            }
+        }
+        messages.extend(self.message);
+        messages.extend(self.error_description);
+        messages
+    }
+}
+
+/// Path and query of the URL for messages, the instance is printed separately
+pub fn request_path(url: &url::Url) -> String {
+    match url.query().filter(|v| !v.is_empty()) {
+        None => url.path().to_owned(),
+        Some(query) => format!("{}?{}", url.path(), query),
+    }
+}
+
+/// Unsuccessful response of Jira or Confluence
+#[derive(Debug)]
+pub struct ApiError {
+    pub kind: ErrorKind,
+    /// Base URL of the instance
+    pub instance: url::Url,
+    pub method: reqwest::Method,
+    pub url: url::Url,
+    pub status: reqwest::StatusCode,
+    /// Messages of the error payload, or the beginning of the body if it isn't a JSON payload
+    pub messages: Vec<String>,
+}
+
+impl ApiError {
+    /// Error of the unsuccessful response with parsed payload
+    pub async fn of_response(
+        instance: &url::Url,
+        method: reqwest::Method,
+        response: reqwest::Response,
+    ) -> Self {
+        let status = response.status();
+        let url = response.url().clone();
+        let body = response.text().await.unwrap_or_default();
+        let messages = match serde_json::from_str::<ErrorPayload>(&body) {
+            Ok(payload) => payload.messages(),
+            // HTML error pages aren't worth printing
+            Err(_) if body.trim_start().starts_with('<') => Vec::new(),
+            Err(_) => Some(body.trim().chars().take(200).collect::<String>())
+                .filter(|v| !v.is_empty())
+                .into_iter()
+                .collect(),
+        };
+        Self {
+            kind: ErrorKind::of_status(status),
+            instance: instance.clone(),
+            method,
+            url,
+            status,
+            messages,
+        }
+    }
+
+    /// Response if it is successful, error with parsed payload otherwise
+    pub async fn check(
+        instance: &url::Url,
+        method: reqwest::Method,
+        response: reqwest::Response,
+    ) -> Result<reqwest::Response, Self> {
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(Self::of_response(instance, method, response).await)
+        }
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = request_path(&self.url);
+        write!(
+            f,
+            "{} {} at {} failed with {}",
+            self.method, path, self.instance, self.status
+        )?;
+        if !self.messages.is_empty() {
+            write!(f, ": {}", self.messages.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiError, ErrorKind, ErrorPayload};
+    use crate::http::test_server;
+
+    fn messages(body: &str) -> Vec<String> {
+        serde_json::from_str::<ErrorPayload>(body)
+            .unwrap()
+            .messages()
+    }
+
+    #[test]
+    fn payload_messages() {
+        assert_eq!(
+            messages(
+                r#"{"errorMessages": ["Bad JQL"], "errors": {"summary": "Required", "due": 1}}"#
+            ),
+            vec!["Bad JQL", "due: 1", "summary: Required"]
+        );
+        assert_eq!(
+            messages(r#"{"statusCode": 404, "message": "No content found"}"#),
+            vec!["No content found"]
+        );
+        assert_eq!(
+            messages(r#"{"error": "invalid_grant", "error_description": "Token is revoked"}"#),
+            vec!["Token is revoked"]
+        );
+        assert!(messages("{}").is_empty());
+    }
+
+    async fn api_error(status: u16, body: &str) -> ApiError {
+        let server = test_server::start(vec![(status, body)]).await;
+        let url = server.url.join("rest/api/2/search?jql=x").unwrap();
+        let response = reqwest::get(url).await.unwrap();
+        ApiError::check(&server.url, reqwest::Method::GET, response)
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn api_error_of_response() {
+        let err = api_error(400, r#"{"errorMessages": ["Bad JQL"]}"#).await;
+        assert_eq!(err.kind, ErrorKind::Validation);
+        assert_eq!(err.messages, vec!["Bad JQL"]);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "GET /rest/api/2/search?jql=x at {} failed with 400 Bad Request: Bad JQL",
+                err.instance
+            )
+        );
+
+        let err = api_error(503, "<html><body>Maintenance</body></html>").await;
+        assert_eq!(err.kind, ErrorKind::Network);
+        assert!(err.messages.is_empty());
+
+        let err = api_error(500, "  Internal failure\n").await;
+        assert_eq!(err.kind, ErrorKind::Other);
+        assert_eq!(err.messages, vec!["Internal failure"]);
+
+        let err = anyhow::Error::new(api_error(401, "").await).context("Failed to log in");
+        assert_eq!(ErrorKind::of_error(&err), ErrorKind::Auth);
+    }
+
+    #[test]
+    fn status_exit_codes() {
+        for (status, kind, code) in [
+            (401, ErrorKind::Auth, 3),
+            (403, ErrorKind::Auth, 3),
+            (404, ErrorKind::NotFound, 4),
+            (400, ErrorKind::Validation, 5),
+            (422, ErrorKind::Validation, 5),
+            (429, ErrorKind::Network, 6),
+            (503, ErrorKind::Network, 6),
+            (500, ErrorKind::Other, 1),
+        ] {
+            let status = reqwest::StatusCode::from_u16(status).unwrap();
+            let actual = ErrorKind::of_status(status);
+            assert_eq!(actual, kind, "{}", status);
+            assert_eq!(actual.exit_code(), code, "{}", status);
+        }
+    }
+}
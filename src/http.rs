@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{format_err, Result};
+use serde::{Deserialize, Serialize};
+
+fn default_timeout() -> u64 {
+    30
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_retry_delay() -> u64 {
+    500
+}
+
+fn default_max_in_flight() -> usize {
+    8
+}
+
+/// Upper bound of the delay between retries when the server doesn't set "Retry-After"
+const MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Client certificate for mutual TLS
+#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+pub struct ClientCertificate {
+    /// PEM file with the certificate chain. Path may start with "~"
+    pub certificate: String,
+    /// PEM file with the PKCS #8 private key. Path may start with "~"
+    pub key: String,
+}
+
+/// HTTP settings of Jira or Confluence instance
+#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+pub struct HttpConfig {
+    /// Request timeout in seconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Proxy for all requests, credentials may be set in the URL
+    #[serde(default)]
+    pub proxy: Option<url::Url>,
+    /// PEM bundle of certificates trusted in addition to the system ones. Path may start with "~"
+    #[serde(default)]
+    pub ca_bundle: Option<String>,
+    #[serde(default)]
+    pub client_certificate: Option<ClientCertificate>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Headers added to every request
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Retries of requests without side effects, e.g. GET, after connection errors and 429, 502,
+    /// 503 or 504 responses
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Delay before the first retry in milliseconds, doubled by every next retry
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u64,
+    /// Maximum number of requests per second to the instance. Unlimited if not set
+    #[serde(default)]
+    pub rate_limit: Option<u32>,
+    /// Maximum number of concurrent requests to the instance
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: default_timeout(),
+            proxy: None,
+            ca_bundle: None,
+            client_certificate: None,
+            user_agent: None,
+            headers: BTreeMap::new(),
+            retries: default_retries(),
+            retry_delay: default_retry_delay(),
+            rate_limit: None,
+            max_in_flight: default_max_in_flight(),
+        }
+    }
+}
+
+/// Rate and concurrency limits of requests to an instance
+struct Limiter {
+    in_flight: Arc<tokio::sync::Semaphore>,
+    interval: Option<std::time::Duration>,
+    next_request: tokio::sync::Mutex<tokio::time::Instant>,
+}
+
+impl Limiter {
+    fn new(config: &HttpConfig) -> Self {
+        Self {
+            in_flight: Arc::new(tokio::sync::Semaphore::new(config.max_in_flight.max(1))),
+            interval: config
+                .rate_limit
+                .filter(|v| *v > 0)
+                .map(|v| std::time::Duration::from_secs(1) / v),
+            next_request: tokio::sync::Mutex::new(tokio::time::Instant::now()),
+        }
+    }
+
+    /// Waits for a free slot. The request is in flight until the permit is dropped
+    async fn acquire(&self) -> Result<tokio::sync::OwnedSemaphorePermit> {
+        let permit = self.in_flight.clone().acquire_owned().await?;
+        if let Some(interval) = self.interval {
+            let mut next_request = self.next_request.lock().await;
+            let at = (*next_request).max(tokio::time::Instant::now());
+            *next_request = at + interval;
+            drop(next_request);
+            tokio::time::sleep_until(at).await;
+        }
+        Ok(permit)
+    }
+}
+
+/// Limiters by base URL of the instance
+static LIMITERS: OnceLock<std::sync::Mutex<HashMap<url::Url, Arc<Limiter>>>> = OnceLock::new();
+
+/// Random part of the retry delay, so parallel requests don't retry at the same moment
+fn jitter(max: std::time::Duration) -> std::time::Duration {
+    use std::hash::{BuildHasher, Hasher};
+
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    let max_ms = max.as_millis().max(1) as u64;
+    std::time::Duration::from_millis(hasher.finish() % max_ms)
+}
+
+/// Delay requested by the server in seconds or as HTTP date
+fn retry_after(response: &reqwest::Response) -> Option<std::time::Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(std::time::Duration::from_secs(seconds));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+fn is_transient_status(status: reqwest::StatusCode) -> bool {
+    matches!(
+        status,
+        reqwest::StatusCode::TOO_MANY_REQUESTS
+            | reqwest::StatusCode::BAD_GATEWAY
+            | reqwest::StatusCode::SERVICE_UNAVAILABLE
+            | reqwest::StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn is_transient_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .map(|v| v.is_timeout() || v.is_connect())
+        .unwrap_or_default()
+}
+
+/// Clients are shared by instances with the same settings, so connections are reused
+static CLIENTS: OnceLock<std::sync::Mutex<HashMap<HttpConfig, reqwest::Client>>> = OnceLock::new();
+
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    let path = shellexpand::tilde(path).to_string();
+    std::fs::read(&path).map_err(|err| format_err!("Failed to read {:?}: {}", path, err))
+}
+
+impl HttpConfig {
+    fn build_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::ClientBuilder::new()
+            .timeout(std::time::Duration::from_secs(self.timeout))
+            .user_agent(
+                self.user_agent
+                    .clone()
+                    .unwrap_or_else(|| format!("uprava/{}", env!("CARGO_PKG_VERSION"))),
+            );
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy.clone())?);
+        }
+
+        if let Some(path) = &self.ca_bundle {
+            let certificates = reqwest::Certificate::from_pem_bundle(&read_file(path)?)
+                .map_err(|err| format_err!("Invalid CA bundle {:?}: {}", path, err))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some(v) = &self.client_certificate {
+            let identity =
+                reqwest::Identity::from_pkcs8_pem(&read_file(&v.certificate)?, &read_file(&v.key)?)
+                    .map_err(|err| {
+                        format_err!("Invalid client certificate {:?}: {}", v.certificate, err)
+                    })?;
+            builder = builder.identity(identity);
+        }
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in &self.headers {
+            let _ = headers.insert(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|err| format_err!("Invalid header name {:?}: {}", name, err))?,
+                reqwest::header::HeaderValue::from_str(value)
+                    .map_err(|err| format_err!("Invalid value of header {:?}: {}", name, err))?,
+            );
+        }
+
+        Ok(builder.default_headers(headers).build()?)
+    }
+
+    /// Shared client with these settings
+    pub fn client(&self) -> Result<reqwest::Client> {
+        let mut clients = CLIENTS
+            .get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+            .lock()
+            .map_err(|_| format_err!("HTTP clients cache is poisoned"))?;
+        if let Some(client) = clients.get(self) {
+            return Ok(client.clone());
+        }
+        let client = self.build_client()?;
+        let _ = clients.insert(self.clone(), client.clone());
+        Ok(client)
+    }
+}
+
+impl HttpConfig {
+    fn limiter(&self, base_url: &url::Url) -> Result<Arc<Limiter>> {
+        let mut limiters = LIMITERS
+            .get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+            .lock()
+            .map_err(|_| format_err!("HTTP limiters cache is poisoned"))?;
+        Ok(limiters
+            .entry(base_url.clone())
+            .or_insert_with(|| Arc::new(Limiter::new(self)))
+            .clone())
+    }
+
+    fn retry_delay(&self, attempt: u32) -> std::time::Duration {
+        let delay = std::time::Duration::from_millis(self.retry_delay)
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_RETRY_DELAY);
+        delay / 2 + jitter(delay / 2)
+    }
+
+    /// Sends the request to the instance with `access` credentials within rate and concurrency
+    /// limits and returns the response body. Requests without side effects are retried with
+    /// exponential backoff after transient failures: a timed out update may have been applied.
+    /// Unsuccessful responses are returned as [`crate::error::ApiError`]
+    pub async fn send(
+        &self,
+        base_url: &url::Url,
+        access: &crate::authentication::Access,
+        request: reqwest::RequestBuilder,
+    ) -> Result<String> {
+        let client = self.client()?;
+        let limiter = self.limiter(base_url)?;
+        let mut request = request.build()?;
+        let method = request.method().clone();
+        let path = crate::error::request_path(request.url());
+
+        let mut attempt = 0;
+        loop {
+            let retry = if method.is_safe() && attempt < self.retries {
+                request.try_clone()
+            } else {
+                None
+            };
+
+            // The request is in flight until its body is read
+            let permit = limiter.acquire().await?;
+            let result = access
+                .send(
+                    &client,
+                    base_url,
+                    reqwest::RequestBuilder::from_parts(client.clone(), request),
+                )
+                .await;
+            // Transient failure and the delay requested by the server
+            let (error, delay) = match result {
+                Ok(response) if is_transient_status(response.status()) => {
+                    let delay = retry_after(&response);
+                    let error =
+                        crate::error::ApiError::of_response(base_url, method.clone(), response)
+                            .await;
+                    (anyhow::Error::from(error), delay)
+                }
+                Ok(response) => {
+                    let response =
+                        crate::error::ApiError::check(base_url, method.clone(), response).await?;
+                    match response.text().await.map_err(anyhow::Error::from) {
+                        Ok(body) => return Ok(body),
+                        Err(err) if is_transient_error(&err) => (err, None),
+                        Err(err) => return Err(err),
+                    }
+                }
+                Err(err) if is_transient_error(&err) => (err, None),
+                Err(err) => return Err(err),
+            };
+            drop(permit);
+
+            request = match retry {
+                Some(v) => v,
+                None if error.is::<crate::error::ApiError>() => return Err(error),
+                None => {
+                    return Err(crate::error::Error::new(
+                        crate::error::ErrorKind::Network,
+                        format!(
+                            "{} {} at {} failed: {}",
+                            method,
+                            path,
+                            base_url,
+                            error.root_cause()
+                        ),
+                    )
+                    .into())
+                }
+            };
+
+            let delay = delay.unwrap_or_else(|| self.retry_delay(attempt));
+            slog_scope::warn!(
+                "Request to {} failed ({}), retry {} of {} in {:.1}s",
+                base_url,
+                error,
+                attempt + 1,
+                self.retries,
+                delay.as_secs_f64()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// URL of the REST API path. Path of `base_url` is kept as a prefix: Atlassian Cloud serves
+/// Confluence under "/wiki" and OAuth 2.0 clients of Jira under "/ex/jira/<cloud id>"
+pub fn api_url(base_url: &url::Url, path: &str) -> url::Url {
+    let mut url = base_url.clone();
+    url.set_path(&format!(
+        "{}/{}",
+        base_url.path().trim_end_matches('/'),
+        path.trim_start_matches('/')
+    ));
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_server, HttpConfig};
+
+    fn config() -> HttpConfig {
+        HttpConfig {
+            retry_delay: 1,
+            ..HttpConfig::default()
+        }
+    }
+
+    fn access() -> crate::authentication::Access {
+        crate::authentication::Access::Token(crate::authentication::Secret::String(
+            "token".to_owned(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn get_is_retried_after_transient_status() {
+        let server = test_server::start(vec![(503, "{}"), (200, r#"{"ok": true}"#)]).await;
+        let config = config();
+        let request = config
+            .client()
+            .unwrap()
+            .get(server.url.join("page").unwrap());
+        let body = config.send(&server.url, &access(), request).await.unwrap();
+        assert_eq!(body, r#"{"ok": true}"#);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn put_is_not_retried() {
+        let server = test_server::start(vec![(503, "{}"), (200, "{}")]).await;
+        let config = config();
+        let request = config
+            .client()
+            .unwrap()
+            .put(server.url.join("page").unwrap())
+            .body("{}");
+        let err = config
+            .send(&server.url, &access(), request)
+            .await
+            .unwrap_err();
+        let err = err.downcast::<crate::error::ApiError>().unwrap();
+        assert_eq!(err.status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_are_limited() {
+        let server = test_server::start(vec![(502, "{}"); 3]).await;
+        let config = HttpConfig {
+            retries: 2,
+            ..config()
+        };
+        let request = config
+            .client()
+            .unwrap()
+            .get(server.url.join("page").unwrap());
+        let err = config
+            .send(&server.url, &access(), request)
+            .await
+            .unwrap_err();
+        assert!(err.is::<crate::error::ApiError>());
+        assert_eq!(server.requests().len(), 3);
+    }
+}
+
+/// Local HTTP server answering requests with prepared responses in order
+#[cfg(test)]
+pub mod test_server {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[derive(Clone, Debug)]
+    pub struct Request {
+        pub method: String,
+        pub path: String,
+        /// Header names are lowercase
+        pub headers: Vec<(String, String)>,
+        pub body: String,
+    }
+
+    impl Request {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.as_str())
+        }
+    }
+
+    pub struct TestServer {
+        pub url: url::Url,
+        pub requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    impl TestServer {
+        pub fn requests(&self) -> Vec<Request> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> Option<Request> {
+        let mut data = Vec::new();
+        let mut buffer = [0; 4096];
+        let header_end = loop {
+            if let Some(i) = data.windows(4).position(|v| v == b"\r\n\r\n") {
+                break i + 4;
+            }
+            let read = stream.read(&mut buffer).await.ok()?;
+            if read == 0 {
+                return None;
+            }
+            data.extend_from_slice(&buffer[..read]);
+        };
+        let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next()?.split(' ');
+        let method = request_line.next()?.to_owned();
+        let path = request_line.next()?.to_owned();
+        let headers: Vec<_> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_owned()))
+            .collect();
+        let length: usize = headers
+            .iter()
+            .find(|(k, _)| k == "content-length")
+            .and_then(|(_, v)| v.parse().ok())
+            .unwrap_or_default();
+        while data.len() < header_end + length {
+            let read = stream.read(&mut buffer).await.ok()?;
+            if read == 0 {
+                break;
+            }
+            data.extend_from_slice(&buffer[..read]);
+        }
+        Some(Request {
+            method,
+            path,
+            headers,
+            body: String::from_utf8_lossy(&data[header_end..]).to_string(),
+        })
+    }
+
+    /// Serves `responses` of status and JSON body, one per connection
+    pub async fn start(responses: Vec<(u16, &str)>) -> TestServer {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = url::Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responses: Vec<_> = responses
+            .into_iter()
+            .map(|(status, body)| (status, body.to_owned()))
+            .collect();
+        let log = requests.clone();
+        drop(tokio::spawn(async move {
+            for (status, body) in responses {
+                let (mut stream, _) = match listener.accept().await {
+                    Ok(v) => v,
+                    Err(_) => return,
+                };
+                if let Some(request) = read_request(&mut stream).await {
+                    log.lock().unwrap().push(request);
+                }
+                let response = format!(
+                    "HTTP/1.1 {} Test\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        }));
+        TestServer { url, requests }
+    }
+}
@@ -1,6 +1,6 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use atlassian_jira_rest_types::v2::Comment;
-use chrono::Datelike;
+use chrono::TimeZone;
 use serde::{Deserialize, Serialize};
 
 use crate::jira_types::IssueBean;
@@ -28,52 +28,456 @@ impl SearchGetParams {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+pub fn parse_jira_datetime(s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f%z")
+        .map(|v| v.with_timezone(&chrono::Utc))
+        .map_err(|err| slog_scope::warn!("Cannot parse date {:?}: {}", s, err))
+        .ok()
+}
+
+/// Timezone and formats of dates shown in reports and printers
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct DateDisplay {
+    /// IANA name like `Europe/Moscow`
+    pub timezone: chrono_tz::Tz,
+    /// `strftime` format of dates
+    pub date_format: String,
+    /// `strftime` format of date and time
+    pub datetime_format: String,
+}
+
+impl Default for DateDisplay {
+    fn default() -> Self {
+        Self {
+            timezone: chrono_tz::UTC,
+            date_format: "%Y-%m-%d".to_owned(),
+            datetime_format: "%Y-%m-%d %H:%M".to_owned(),
+        }
+    }
+}
+
+impl DateDisplay {
+    pub fn validate(&self) -> Result<()> {
+        for format in [&self.date_format, &self.datetime_format] {
+            let items = chrono::format::StrftimeItems::new(format);
+            if items.into_iter().any(|v| v == chrono::format::Item::Error) {
+                bail!("Invalid format {:?} in dates", format);
+            }
+        }
+        Ok(())
+    }
+
+    /// Current date in the timezone
+    pub fn today(&self) -> chrono::NaiveDate {
+        self.date(chrono::Utc::now())
+    }
+
+    /// Date of the moment in the timezone
+    pub fn date(&self, moment: chrono::DateTime<chrono::Utc>) -> chrono::NaiveDate {
+        moment.with_timezone(&self.timezone).date_naive()
+    }
+
+    pub fn format_date(&self, moment: chrono::DateTime<chrono::Utc>) -> String {
+        self.date(moment).format(&self.date_format).to_string()
+    }
+
+    pub fn format_datetime(&self, moment: chrono::DateTime<chrono::Utc>) -> String {
+        moment
+            .with_timezone(&self.timezone)
+            .format(&self.datetime_format)
+            .to_string()
+    }
+
+    /// Moment of a date field value `YYYY-MM-DD`, which is the start of the day in the timezone,
+    /// or of a datetime field value
+    pub fn parse(&self, s: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+        match chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Ok(date) => self.start_of_day(date),
+            Err(_) => parse_jira_datetime(s),
+        }
+    }
+
+    /// Moment the date starts in the timezone
+    pub fn start_of_day(&self, date: chrono::NaiveDate) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.timezone
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .earliest()
+            .map(|v| v.with_timezone(&chrono::Utc))
+    }
+}
+
+#[derive(Serialize, Clone, Hash, PartialEq, Eq, Debug)]
 pub struct CustomField {
+    /// Field ID, or display name until the config is resolved against the instance
     pub name: String,
 }
 
+// Fields are written either as `{name: customfield_10000}` or as a plain ID or display name
+impl<'de> Deserialize<'de> for CustomField {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Name(String),
+            Struct { name: String },
+        }
+
+        let name = match Repr::deserialize(deserializer)? {
+            Repr::Name(v) => v,
+            Repr::Struct { name } => name,
+        };
+        Ok(Self { name })
+    }
+}
+
 impl CustomField {
-    pub fn of_issue(&self, issue: &crate::jira_types::IssueBean) -> Result<Option<String>> {
+    /// Pseudo fields handled by [`CustomField::of_issue`] without the fields list
+    const PSEUDO_FIELDS: [&'static str; 5] =
+        ["summary", "description", "key", "id", "issuetype.name"];
+
+    /// ID of a custom field or a pseudo field, doesn't need resolution by display name
+    pub fn is_id(&self) -> bool {
+        let custom_id = self
+            .name
+            .strip_prefix("customfield_")
+            .map(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_digit()));
+        custom_id.unwrap_or_else(|| Self::PSEUDO_FIELDS.contains(&self.name.as_str()))
+    }
+
+    /// Replaces display name with field ID. IDs of the instance are kept as is
+    pub fn resolve(
+        &mut self,
+        instance: &str,
+        fields: &[atlassian_jira_rest_types::v2::FieldDetails],
+    ) -> Result<()> {
+        if self.is_id() || fields.iter().any(|v| v.id.as_ref() == Some(&self.name)) {
+            return Ok(());
+        }
+        let ids: Vec<_> = fields
+            .iter()
+            .filter(|v| v.name.as_ref() == Some(&self.name))
+            .filter_map(|v| v.id.as_deref())
+            .collect();
+        match ids.as_slice() {
+            [] => Err(crate::error::Error::new(
+                crate::error::ErrorKind::Validation,
+                format!(
+                    "Field {:?} is not found in Jira instance {:?}, see `uprava jira fields`",
+                    self.name, instance
+                ),
+            )
+            .into()),
+            [id] => {
+                slog_scope::debug!("Field {:?} of {:?} is {}", self.name, instance, id);
+                self.name = id.to_string();
+                Ok(())
+            }
+            ids => Err(crate::error::Error::new(
+                crate::error::ErrorKind::Validation,
+                format!(
+                    "Field name {:?} is ambiguous in Jira instance {:?}, use one of IDs: {}",
+                    self.name,
+                    instance,
+                    ids.join(", ")
+                ),
+            )
+            .into()),
+        }
+    }
+
+    pub fn of_issue(
+        &self,
+        issue: &crate::jira_types::IssueBean,
+        dates: &DateDisplay,
+    ) -> Result<Option<String>> {
         let r = match self.name.as_str() {
             "summary" => Some(issue.fields.summary.clone()),
             "description" => issue.fields.description.clone(),
             "key" => Some(issue.key.clone()),
             "id" => Some(issue.id.clone()),
             "issuetype.name" => issue.fields.issuetype.as_ref().and_then(|v| v.name.clone()),
-            v => match issue.fields.custom_fields.get(v) {
-                None => None,
-                Some(v) => serde_json::value::from_value(v.clone())?,
-            },
+            v => issue.field_value(v, dates).map(|v| field_text(&v)),
         };
 
         Ok(r)
     }
 
+    /// Key of the referenced issue. Advanced Roadmaps "Parent Link" is an object with the key
+    /// inside, other fields are plain keys
+    pub fn key_of_issue(&self, issue: &crate::jira_types::IssueBean) -> Option<String> {
+        let value = issue.fields.custom_fields.get(&self.name)?;
+        match value {
+            serde_json::Value::String(v) => Some(v.clone()),
+            serde_json::Value::Object(v) => v
+                .get("data")
+                .and_then(|v| v.get("key"))
+                .or_else(|| v.get("key"))
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned()),
+            _ => None,
+        }
+    }
+
+    /// Field reference in JQL, `cf[10000]` for `customfield_10000`
+    pub fn jql_name(&self) -> String {
+        match self.name.strip_prefix("customfield_") {
+            Some(id) => format!("cf[{}]", id),
+            None => format!("\"{}\"", self.name),
+        }
+    }
+
+    /// Moment of a date or datetime field value
     pub fn date_of_issue(
         &self,
         issue: &crate::jira_types::IssueBean,
+        dates: &DateDisplay,
     ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
-        let s: Option<String> = self.of_issue(issue)?;
-        match s {
-            None => Ok(None),
-            Some(v) => {
-                let v = format!("\"{}T00:00:00.000000Z\"", v);
-                slog_scope::debug!("Parsing DateTime from string: {}", v);
-                let r: chrono::DateTime<chrono::Utc> = serde_json::from_str(&v)?;
-                Ok(Some(r))
+        match issue.fields.get(&self.name) {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(serde_json::Value::String(v)) => match dates.parse(&v) {
+                None => bail!(
+                    "Cannot parse date {:?} of field {} in issue {}",
+                    v,
+                    self.name,
+                    issue.key
+                ),
+                v => Ok(v),
+            },
+            Some(v) => bail!(
+                "Field {} in issue {} is not a date: {}",
+                self.name,
+                issue.key,
+                v
+            ),
+        }
+    }
+}
+
+/// Type of field values, defines how they are shown
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Number,
+    /// `YYYY-MM-DD`
+    Date,
+    /// Date and time with timezone, shown as `YYYY-MM-DD HH:MM`
+    DateTime,
+    User,
+    /// Select list, values are shown by their names
+    Option,
+    /// Select list with dependent child options, shown as `parent / child`
+    Cascading,
+    Version,
+    /// Multi-select, labels, sprints and other lists
+    Array,
+}
+
+impl FieldType {
+    /// Type of values by the field schema from `/rest/api/2/field` or `expand=schema`, `None`
+    /// for types decoded by the value shape
+    pub fn of_schema(schema: &atlassian_jira_rest_types::v2::JsonTypeBean) -> Option<Self> {
+        let r = match schema.type_.as_str() {
+            "string" => FieldType::String,
+            "number" => FieldType::Number,
+            "date" => FieldType::Date,
+            "datetime" => FieldType::DateTime,
+            "user" => FieldType::User,
+            "option" | "priority" | "status" | "resolution" | "issuetype" | "securitylevel"
+            | "project" | "component" => FieldType::Option,
+            "option-with-child" => FieldType::Cascading,
+            "version" => FieldType::Version,
+            "array" => FieldType::Array,
+            _ => return None,
+        };
+        Some(r)
+    }
+
+    /// Type of values of fields without schema
+    pub fn of_value(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Number(_) => FieldType::Number,
+            serde_json::Value::Array(_) => FieldType::Array,
+            serde_json::Value::Object(v) if v.contains_key("child") => FieldType::Cascading,
+            serde_json::Value::Object(v) if v.contains_key("displayName") => FieldType::User,
+            serde_json::Value::Object(_) => FieldType::Option,
+            _ => FieldType::String,
+        }
+    }
+
+    /// Text of an option, user, version or other object. Jira Server sends sprints as strings
+    /// like `com.atlassian.greenhopper.service.sprint.Sprint@1f[id=1,...,name=Sprint 1,...]`
+    fn object_text(value: &serde_json::Value) -> Option<serde_json::Value> {
+        match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::Object(v) => ["value", "displayName", "name", "key"]
+                .iter()
+                .find_map(|k| v.get(*k).filter(|v| !v.is_null()))
+                .cloned()
+                .or_else(|| {
+                    // Advanced Roadmaps "Parent Link"
+                    v.get("data").and_then(|v| v.get("key")).cloned()
+                })
+                .or_else(|| Some(value.to_string().into())),
+            serde_json::Value::String(v) if v.starts_with("com.atlassian.greenhopper.") => {
+                let name = v
+                    .split_once("[")
+                    .and_then(|(_, v)| v.split(',').find_map(|v| v.strip_prefix("name=")));
+                Some(name.unwrap_or(v).into())
             }
+            v => Some(v.clone()),
+        }
+    }
+
+    /// Value as a string, number or array of them. Dates are shown in the display timezone and
+    /// format
+    pub fn decode(
+        &self,
+        value: &serde_json::Value,
+        dates: &DateDisplay,
+    ) -> Option<serde_json::Value> {
+        match (self, value) {
+            (_, serde_json::Value::Null) => None,
+            (_, serde_json::Value::String(v)) if v.starts_with("com.atlassian.greenhopper.") => {
+                Self::object_text(value)
+            }
+            (FieldType::Number, serde_json::Value::String(v)) => {
+                v.trim().parse::<f64>().ok().map(|v| v.into())
+            }
+            (FieldType::Date, serde_json::Value::String(v)) => {
+                match chrono::NaiveDate::parse_from_str(v, "%Y-%m-%d") {
+                    Err(_) => Some(value.clone()),
+                    Ok(v) => Some(v.format(&dates.date_format).to_string().into()),
+                }
+            }
+            (FieldType::DateTime, serde_json::Value::String(v)) => match parse_jira_datetime(v) {
+                None => Some(value.clone()),
+                Some(v) => Some(dates.format_datetime(v).into()),
+            },
+            (FieldType::Cascading, serde_json::Value::Object(v)) => {
+                let parent = v.get("value").and_then(|v| v.as_str()).unwrap_or_default();
+                match v
+                    .get("child")
+                    .and_then(|v| v.get("value"))
+                    .and_then(|v| v.as_str())
+                {
+                    None => Some(parent.into()),
+                    Some(child) => Some(format!("{} / {}", parent, child).into()),
+                }
+            }
+            (FieldType::Array, serde_json::Value::Array(v)) => Some(serde_json::Value::Array(
+                v.iter()
+                    .filter_map(|v| Self::of_value(v).decode(v, dates))
+                    .collect(),
+            )),
+            (
+                FieldType::String | FieldType::Date | FieldType::DateTime | FieldType::Number,
+                v @ (serde_json::Value::String(_) | serde_json::Value::Number(_)),
+            ) => Some(v.clone()),
+            (_, v) => Self::object_text(v),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+/// Decoded value as text: numbers without trailing zeroes, arrays joined by commas
+pub fn field_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(v) => v.clone(),
+        serde_json::Value::Number(v) => match v.as_f64() {
+            Some(v) if v.fract() == 0.0 && v.abs() < 1e15 => format!("{}", v as i64),
+            _ => v.to_string(),
+        },
+        serde_json::Value::Array(v) => v.iter().map(field_text).collect::<Vec<_>>().join(", "),
+        v => v.to_string(),
+    }
+}
+
+/// Field of an instance under a name shared by instances, so the same report config works with
+/// instances having different custom field IDs
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FieldAlias {
+    pub field: CustomField,
+    /// Type of values, the field schema is used if not set
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<FieldType>,
+}
+
+impl FieldAlias {
+    pub fn value(
+        &self,
+        issue: &crate::jira_types::IssueBean,
+        dates: &DateDisplay,
+    ) -> Option<serde_json::Value> {
+        match self.kind {
+            None => issue.field_value(&self.field.name, dates),
+            Some(kind) => kind.decode(&issue.fields.get(&self.field.name)?, dates),
+        }
+    }
+}
+
+/// Fields used by reports, the ones not set are never shown
+#[derive(Serialize, Deserialize, Clone, Default, Hash, PartialEq, Eq, Debug)]
+#[serde(default)]
 pub struct IssueCustomFieldsConfig {
-    pub reason: CustomField,
-    pub epic_link: CustomField,
-    pub epic_name: CustomField,
-    pub planned_start: CustomField,
-    pub planned_end: CustomField,
+    pub reason: Option<CustomField>,
+    pub epic_link: Option<CustomField>,
+    pub epic_name: Option<CustomField>,
+    pub planned_start: Option<CustomField>,
+    pub planned_end: Option<CustomField>,
+    /// Advanced Roadmaps "Parent Link" linking epics to initiatives
+    pub parent_link: Option<CustomField>,
+}
+
+impl IssueCustomFieldsConfig {
+    /// Mapped fields by their names in config
+    pub fn fields(&self) -> Vec<(&'static str, &CustomField)> {
+        [
+            ("reason", &self.reason),
+            ("epic_link", &self.epic_link),
+            ("epic_name", &self.epic_name),
+            ("planned_start", &self.planned_start),
+            ("planned_end", &self.planned_end),
+            ("parent_link", &self.parent_link),
+        ]
+        .into_iter()
+        .filter_map(|(name, field)| field.as_ref().map(|v| (name, v)))
+        .collect()
+    }
+
+    fn fields_mut(&mut self) -> impl Iterator<Item = &mut CustomField> {
+        [
+            &mut self.reason,
+            &mut self.epic_link,
+            &mut self.epic_name,
+            &mut self.planned_start,
+            &mut self.planned_end,
+            &mut self.parent_link,
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// All fields are IDs, so the fields list of the instance isn't needed
+    pub fn is_resolved(&self) -> bool {
+        self.fields().iter().all(|(_, v)| v.is_id())
+    }
+
+    /// Replaces display names with field IDs
+    pub fn resolve(
+        &mut self,
+        instance: &str,
+        fields: &[atlassian_jira_rest_types::v2::FieldDetails],
+    ) -> Result<()> {
+        for field in self.fields_mut() {
+            field.resolve(instance, fields)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -83,91 +487,190 @@ pub struct IssueCustomFields {
     pub epic_name: Option<String>,
     pub planned_start: Option<chrono::DateTime<chrono::Utc>>,
     pub planned_end: Option<chrono::DateTime<chrono::Utc>>,
+    pub parent_link: Option<String>,
 }
 
 impl IssueCustomFields {
     pub fn of_issue(jira: &JiraServer, issue: &crate::jira_types::IssueBean) -> Result<Self> {
+        let fields = &jira.custom_fields;
+        let of_issue = |field: &Option<CustomField>| match field {
+            None => Ok(None),
+            Some(v) => v.of_issue(issue, &jira.dates),
+        };
+        let date_of_issue = |field: &Option<CustomField>| match field {
+            None => Ok(None),
+            Some(v) => v.date_of_issue(issue, &jira.dates),
+        };
         Ok(Self {
-            reason: jira.custom_fields.reason.of_issue(issue)?,
-            epic_link: jira.custom_fields.epic_link.of_issue(issue)?,
-            epic_name: jira.custom_fields.epic_name.of_issue(issue)?,
-            planned_start: jira.custom_fields.planned_start.date_of_issue(issue)?,
-            planned_end: jira.custom_fields.planned_end.date_of_issue(issue)?,
+            reason: of_issue(&fields.reason)?,
+            epic_link: of_issue(&fields.epic_link)?,
+            epic_name: of_issue(&fields.epic_name)?,
+            planned_start: date_of_issue(&fields.planned_start)?,
+            planned_end: date_of_issue(&fields.planned_end)?,
+            parent_link: jira
+                .custom_fields
+                .parent_link
+                .as_ref()
+                .and_then(|v| v.key_of_issue(issue)),
         })
     }
 
-    fn format_date(date: &Option<chrono::DateTime<chrono::Utc>>) -> String {
-        match date {
+    pub fn plan(&self, dates: &DateDisplay) -> String {
+        let format_date = |date: Option<chrono::DateTime<chrono::Utc>>| match date {
             None => "?".to_owned(),
-            Some(v) => format!("{}-{:02}-{:02}", v.year(), v.month(), v.day()),
-        }
-    }
-
-    pub fn plan(&self) -> String {
+            Some(v) => dates.format_date(v),
+        };
         if self.planned_start.is_some() || self.planned_end.is_some() {
             format!(
                 "{} - {}",
-                Self::format_date(&self.planned_start),
-                Self::format_date(&self.planned_end)
+                format_date(self.planned_start),
+                format_date(self.planned_end)
             )
         } else {
             "".to_owned()
         }
     }
-}
 
-#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
-pub struct RelationMap {
-    pub name: String,
-    pub equals_to: String,
+    /// Color of the plan: red if planned end is in 3 days or passed, green if planned start is
+    pub fn plan_color(&self, dates: &DateDisplay) -> Option<&'static str> {
+        let soon = |date: Option<chrono::DateTime<chrono::Utc>>| {
+            date.is_some_and(|v| dates.date(v) - chrono::Duration::days(3) <= dates.today())
+        };
+        if soon(self.planned_end) {
+            Some("red")
+        } else if soon(self.planned_start) {
+            Some("green")
+        } else {
+            None
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct JiraServer {
+    /// Name of the instance in config file, or base URL for unnamed ones. Set when config loads
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
     pub base_url: url::Url,
     #[serde(with = "serde_yaml::with::singleton_map")]
     pub access: crate::authentication::Access,
+    #[serde(default)]
     pub custom_fields: IssueCustomFieldsConfig,
+    /// Fields by names shared between instances, e.g. `story_points`
+    #[serde(default, rename = "fields")]
+    pub field_aliases: std::collections::BTreeMap<String, FieldAlias>,
+    #[serde(default = "crate::report::default_relation_kinds")]
+    pub relation_kinds: Vec<crate::report::RelationKind>,
     #[serde(default)]
-    pub relations_map: Vec<RelationMap>,
+    pub http: crate::http::HttpConfig,
+    /// Copy of the top-level `dates`. Set when config loads
+    #[serde(default)]
+    pub dates: DateDisplay,
+}
+
+// Instances are identified by name, so copies with different secrets or settings are the same
+impl PartialEq for JiraServer {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for JiraServer {}
+
+impl std::hash::Hash for JiraServer {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
 }
 
 impl JiraServer {
+    pub fn field_alias(&self, name: &str) -> Option<&FieldAlias> {
+        self.field_aliases.get(name)
+    }
+
+    /// Field ID of the alias, or the name itself if it isn't an alias
+    pub fn field_id<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.field_alias(name) {
+            None => name,
+            Some(v) => &v.field.name,
+        }
+    }
+
+    /// Custom fields and aliases are IDs, so the fields list isn't needed
+    pub fn is_fields_resolved(&self) -> bool {
+        self.custom_fields.is_resolved() && self.field_aliases.values().all(|v| v.field.is_id())
+    }
+
+    /// Replaces display names of custom fields and aliases with field IDs
+    pub fn resolve_fields(
+        &mut self,
+        fields: &[atlassian_jira_rest_types::v2::FieldDetails],
+    ) -> Result<()> {
+        self.custom_fields.resolve(&self.name, fields)?;
+        for alias in self.field_aliases.values_mut() {
+            alias.field.resolve(&self.name, fields)?;
+        }
+        Ok(())
+    }
+
+    /// Kind of relation by issue link name and whether "A <link name> B" means relation from A
+    /// to B
+    pub fn relation_kind(&self, link_name: &str) -> Option<(&crate::report::RelationKind, bool)> {
+        self.relation_kinds.iter().find_map(|kind| {
+            if kind.outward.iter().any(|v| v == link_name) {
+                Some((kind, true))
+            } else if kind.inward.iter().any(|v| v == link_name) {
+                Some((kind, false))
+            } else {
+                None
+            }
+        })
+    }
+
     pub async fn http_get(&self, path: &str, params: &[(&str, &str)]) -> Result<String> {
-        let http_client = reqwest::ClientBuilder::new()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()?;
-        let mut url = self.base_url.clone();
-        url.set_path(path);
+        let http_client = self.http.client()?;
+        let mut url = crate::http::api_url(&self.base_url, path);
         url.query_pairs_mut().extend_pairs(params);
 
         let request = http_client
             .get(url.clone())
             .header(reqwest::header::ACCEPT, "application/json")
             .header(reqwest::header::CONTENT_TYPE, "application/json");
-        let request = match &self.access {
-            crate::authentication::Access::Token(secret) => request.header(
-                reqwest::header::AUTHORIZATION,
-                format!("Bearer {}", secret.get()?.trim()),
-            ),
-            crate::authentication::Access::JSessionID(secret) => request.header(
-                reqwest::header::COOKIE,
-                format!("JSESSIONID={}", secret.get()?.trim()),
-            ),
-        };
-
         slog_scope::debug!("Querying JIRA URL: {}", url);
 
-        let response = request.send().await?.error_for_status()?.text().await?;
+        let response = self
+            .http
+            .send(&self.base_url, &self.access, request)
+            .await?;
 
         Ok(response)
     }
 
+    /// User the instance is accessed as
+    pub async fn myself(&self) -> Result<atlassian_jira_rest_types::v2::User> {
+        let response = self.http_get("/rest/api/2/myself", &[]).await?;
+        let json = serde_json::de::from_str::<atlassian_jira_rest_types::v2::User>(&response)?;
+        Ok(json)
+    }
+
+    /// System and custom fields of the instance
+    pub async fn fields(&self) -> Result<Vec<atlassian_jira_rest_types::v2::FieldDetails>> {
+        slog_scope::info!("Getting fields of {:?}", self.base_url);
+        let response = self.http_get("/rest/api/2/field", &[]).await?;
+        let json = serde_json::de::from_str::<Vec<atlassian_jira_rest_types::v2::FieldDetails>>(
+            &response,
+        )?;
+        Ok(json)
+    }
+
     pub async fn issue_bean(&self, issue: &str) -> Result<crate::jira_types::IssueBean> {
         slog_scope::info!("Getting issue from {:?}: {:?}", self.base_url, issue);
 
         let response = self
-            .http_get(&format!("/rest/api/2/issue/{}", issue), &[])
+            .http_get(
+                &format!("/rest/api/2/issue/{}", issue),
+                &[("expand", "names,schema")],
+            )
             .await?;
 
         slog_scope::trace!("Got from {:?}: {:?}", self.base_url, response);
@@ -185,7 +688,7 @@ impl JiraServer {
             query.push(("startAt", format!("{}", v)))
         }
         if let Some(v) = params.max_results {
-            query.push(("maxResult", format!("{}", v)))
+            query.push(("maxResults", format!("{}", v)))
         }
         if let Some(v) = params.validate_query {
             query.push(("validateQuery", format!("{}", v)))
@@ -193,8 +696,18 @@ impl JiraServer {
         if let Some(v) = &params.fields {
             query.push(("fields", v.join(",")))
         }
-        if let Some(v) = &params.expand {
-            query.push(("expand", v.join(",")))
+        // Schema is needed to decode custom field values, names to show them. Queries counting
+        // issues get no issues to decode
+        let mut expand = params.expand.clone().unwrap_or_default();
+        if params.max_results != Some(0) {
+            for v in ["names", "schema"] {
+                if !expand.iter().any(|e| e == v) {
+                    expand.push(v.to_owned());
+                }
+            }
+        }
+        if !expand.is_empty() {
+            query.push(("expand", expand.join(",")));
         }
 
         let query: Vec<(&str, &str)> = query.iter().map(|(k, v)| (*k, v.as_str())).collect();
@@ -205,8 +718,19 @@ impl JiraServer {
             .http_get("/rest/api/2/search", query.as_slice())
             .await?;
 
-        let json =
+        let mut json =
             serde_json::de::from_str::<atlassian_jira_rest_types::v2::SearchResults>(&response)?;
+        for issue in json.issues.iter_mut().flatten() {
+            let has_value = |k: &String| issue.fields.get(k).is_some_and(|v| !v.is_null());
+            if let Some(names) = &json.names {
+                let names = names.iter().filter(|(k, _)| has_value(k));
+                issue.names = Some(names.map(|(k, v)| (k.clone(), v.clone())).collect());
+            }
+            if let Some(schema) = &json.schema {
+                let schema = schema.iter().filter(|(k, _)| has_value(k));
+                issue.schema = Some(schema.map(|(k, v)| (k.clone(), v.clone())).collect());
+            }
+        }
         Ok(json)
     }
 
@@ -245,11 +769,12 @@ impl JiraServer {
 
 pub enum CommentPrinter {
     Email,
+    #[allow(dead_code)]
     Serde(crate::printer::SerdePrinter),
 }
 
 impl CommentPrinter {
-    fn printer_email(&self, comment: &Comment) -> Result<String> {
+    fn printer_email(&self, comment: &Comment, dates: &DateDisplay) -> Result<String> {
         use std::fmt::Write;
         let mut output = String::new();
         writeln!(
@@ -270,7 +795,11 @@ impl CommentPrinter {
                 .as_deref()
                 .unwrap_or("no-email"),
         )?;
-        writeln!(&mut output, "Date: {}", comment.created)?;
+        writeln!(
+            &mut output,
+            "Date: {}",
+            Self::format_date(&comment.created, dates)
+        )?;
         if let Some(update_author) = &comment.update_author {
             writeln!(
                 &mut output,
@@ -283,16 +812,27 @@ impl CommentPrinter {
             )?;
         }
         if let Some(updated) = &comment.updated {
-            writeln!(&mut output, "UpdatedAt: {}", updated)?
+            writeln!(
+                &mut output,
+                "UpdatedAt: {}",
+                Self::format_date(updated, dates)
+            )?
         }
         writeln!(&mut output)?;
         writeln!(&mut output, "{}", comment.body)?;
         Ok(output)
     }
 
-    pub fn data_to_string(&self, comment: &Comment) -> Result<String> {
+    fn format_date(date: &str, dates: &DateDisplay) -> String {
+        match parse_jira_datetime(date) {
+            None => date.to_owned(),
+            Some(v) => dates.format_datetime(v),
+        }
+    }
+
+    pub fn data_to_string(&self, comment: &Comment, dates: &DateDisplay) -> Result<String> {
         let r = match self {
-            Self::Email => self.printer_email(comment)?,
+            Self::Email => self.printer_email(comment, dates)?,
             Self::Serde(printer) => printer.data_to_string(comment)?,
         };
         Ok(r)
@@ -306,7 +846,18 @@ pub enum IssuePrinter {
 }
 
 impl IssuePrinter {
-    fn printer_email(&self, issue: &IssueBean) -> Result<String> {
+    /// Decoded values of field aliases set on the issue
+    fn aliases<'a>(
+        jira: &'a JiraServer,
+        issue: &IssueBean,
+    ) -> std::collections::BTreeMap<&'a str, serde_json::Value> {
+        jira.field_aliases
+            .iter()
+            .filter_map(|(name, alias)| Some((name.as_str(), alias.value(issue, &jira.dates)?)))
+            .collect()
+    }
+
+    fn printer_email(&self, jira: &JiraServer, issue: &IssueBean) -> Result<String> {
         use std::fmt::Write;
         let mut output = String::new();
         writeln!(
@@ -333,7 +884,15 @@ impl IssuePrinter {
                 assignee.email_address.as_deref().unwrap_or("no-email"),
             )?;
         }
+        writeln!(
+            &mut output,
+            "Date: {}",
+            CommentPrinter::format_date(&issue.fields.created, &jira.dates)
+        )?;
         writeln!(&mut output, "Subject: {}", issue.fields.summary)?;
+        for (name, value) in Self::aliases(jira, issue) {
+            writeln!(&mut output, "X-Field-{}: {}", name, field_text(&value))?;
+        }
         writeln!(&mut output)?;
         writeln!(
             &mut output,
@@ -347,17 +906,29 @@ impl IssuePrinter {
                 writeln!(
                     &mut output,
                     "\n{}",
-                    comment_printer.data_to_string(comment)?
+                    comment_printer.data_to_string(comment, &jira.dates)?
                 )?
             }
         }
         Ok(output)
     }
 
-    pub fn data_to_string(&self, issue: &IssueBean) -> Result<String> {
+    pub fn data_to_string(&self, jira: &JiraServer, issue: &IssueBean) -> Result<String> {
+        /// The issue with values of field aliases
+        #[derive(Serialize)]
+        struct IssueWithAliases<'a> {
+            #[serde(flatten)]
+            issue: &'a IssueBean,
+            #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+            aliases: std::collections::BTreeMap<&'a str, serde_json::Value>,
+        }
+
         let r = match self {
-            Self::Email => self.printer_email(issue)?,
-            Self::Serde(printer) => printer.data_to_string(issue)?,
+            Self::Email => self.printer_email(jira, issue)?,
+            Self::Serde(printer) => printer.data_to_string(&IssueWithAliases {
+                issue,
+                aliases: Self::aliases(jira, issue),
+            })?,
         };
         Ok(r)
     }
@@ -373,3 +944,159 @@ impl std::str::FromStr for IssuePrinter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{DateDisplay, FieldType, JiraServer, SearchGetParams};
+    use crate::http::test_server;
+
+    fn decode(kind: FieldType, value: serde_json::Value) -> Option<serde_json::Value> {
+        kind.decode(&value, &DateDisplay::default())
+    }
+
+    #[test]
+    fn decode_scalars() {
+        assert_eq!(decode(FieldType::String, json!(null)), None);
+        assert_eq!(
+            decode(FieldType::String, json!("text")),
+            Some(json!("text"))
+        );
+        assert_eq!(decode(FieldType::Number, json!(" 5.5 ")), Some(json!(5.5)));
+        assert_eq!(decode(FieldType::Number, json!(3)), Some(json!(3)));
+    }
+
+    #[test]
+    fn decode_dates_in_timezone() {
+        let dates = DateDisplay {
+            timezone: chrono_tz::Asia::Tokyo,
+            date_format: "%d.%m.%Y".to_owned(),
+            datetime_format: "%d.%m.%Y %H:%M".to_owned(),
+        };
+        assert_eq!(
+            FieldType::Date.decode(&json!("2022-10-05"), &dates),
+            Some(json!("05.10.2022"))
+        );
+        assert_eq!(
+            FieldType::DateTime.decode(&json!("2022-10-05T12:30:00.000+0000"), &dates),
+            Some(json!("05.10.2022 21:30"))
+        );
+        assert_eq!(
+            FieldType::Date.decode(&json!("not a date"), &dates),
+            Some(json!("not a date"))
+        );
+    }
+
+    #[test]
+    fn decode_objects() {
+        assert_eq!(
+            decode(
+                FieldType::User,
+                json!({"name": "alice", "displayName": "Alice"})
+            ),
+            Some(json!("Alice"))
+        );
+        assert_eq!(
+            decode(FieldType::Option, json!({"id": "1", "value": "High"})),
+            Some(json!("High"))
+        );
+        assert_eq!(
+            decode(FieldType::Version, json!({"id": "1", "name": "1.0"})),
+            Some(json!("1.0"))
+        );
+        assert_eq!(
+            decode(
+                FieldType::Cascading,
+                json!({"value": "Backend", "child": {"value": "API"}})
+            ),
+            Some(json!("Backend / API"))
+        );
+        assert_eq!(
+            decode(FieldType::Cascading, json!({"value": "Backend"})),
+            Some(json!("Backend"))
+        );
+        assert_eq!(
+            decode(FieldType::Option, json!({"data": {"key": "T-1"}})),
+            Some(json!("T-1"))
+        );
+    }
+
+    #[test]
+    fn decode_arrays_and_sprints() {
+        assert_eq!(
+            decode(
+                FieldType::Array,
+                json!([{"value": "A"}, null, "B", {"name": "C"}])
+            ),
+            Some(json!(["A", "B", "C"]))
+        );
+        assert_eq!(
+            decode(
+                FieldType::Array,
+                json!([
+                    "com.atlassian.greenhopper.service.sprint.Sprint@1f[id=1,state=ACTIVE,name=Sprint 7,goal=]"
+                ])
+            ),
+            Some(json!(["Sprint 7"]))
+        );
+    }
+
+    fn jira(server: &test_server::TestServer) -> JiraServer {
+        serde_yaml::from_str(&format!(
+            "name: test\nbase_url: {}\naccess:\n  Token:\n    String: token\n",
+            server.url
+        ))
+        .unwrap()
+    }
+
+    fn query(request: &test_server::Request) -> Vec<(String, String)> {
+        let url = url::Url::parse("http://localhost")
+            .unwrap()
+            .join(&request.path)
+            .unwrap();
+        url.query_pairs()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn search_counting_issues_skips_expand() {
+        let server = test_server::start(vec![(
+            200,
+            r#"{"startAt": 0, "maxResults": 0, "total": 3}"#,
+        )])
+        .await;
+        let params = SearchGetParams {
+            max_results: Some(0),
+            validate_query: Some(true),
+            ..SearchGetParams::new("project = T")
+        };
+        let result = jira(&server).search(&params).await.unwrap();
+        assert_eq!(result.total, Some(3));
+        assert_eq!(
+            query(&server.requests()[0]),
+            vec![
+                ("jql".to_owned(), "project = T".to_owned()),
+                ("maxResults".to_owned(), "0".to_owned()),
+                ("validateQuery".to_owned(), "true".to_owned()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn search_expands_names_and_schema() {
+        let server = test_server::start(vec![(
+            200,
+            r#"{"startAt": 0, "maxResults": 50, "total": 0}"#,
+        )])
+        .await;
+        let params = SearchGetParams {
+            expand: Some(vec!["changelog".to_owned()]),
+            ..SearchGetParams::new("project = T")
+        };
+        let _ = jira(&server).search(&params).await.unwrap();
+        assert!(query(&server.requests()[0])
+            .contains(&("expand".to_owned(), "changelog,names,schema".to_owned())));
+    }
+}
@@ -20,6 +20,9 @@ pub struct IssueBeanFields {
     pub issuetype: Option<atlassian_jira_rest_types::v2::IssueTypeDetails>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub labels: Option<Vec<String>>,
+    /// Parent of a sub-task
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<atlassian_jira_rest_types::v2::LinkedIssue>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priority: Option<atlassian_jira_rest_types::v2::Priority>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -30,6 +33,8 @@ pub struct IssueBeanFields {
     pub resolutiondate: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<atlassian_jira_rest_types::v2::StatusDetails>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subtasks: Option<Vec<atlassian_jira_rest_types::v2::LinkedIssue>>,
     pub summary: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated: Option<String>,
@@ -39,6 +44,17 @@ pub struct IssueBeanFields {
 }
 
 impl IssueBeanFields {
+    /// Raw value of a custom or system field by its ID
+    pub fn get(&self, id: &str) -> Option<serde_json::Value> {
+        if id.starts_with("customfield_") {
+            return self.custom_fields.get(id).cloned();
+        }
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(mut v)) => v.remove(id),
+            _ => None,
+        }
+    }
+
     pub fn of_json(value: std::collections::BTreeMap<String, serde_json::Value>) -> Result<Self> {
         Ok(Self {
             assignee: crate::serde::json_de_kv_opt(&value, "assignee")?,
@@ -51,11 +67,13 @@ impl IssueBeanFields {
             issuelinks: crate::serde::json_de_kv_opt(&value, "issuelinks")?,
             issuetype: crate::serde::json_de_kv_opt(&value, "issuetype")?,
             labels: crate::serde::json_de_kv_opt(&value, "labels")?,
+            parent: crate::serde::json_de_kv_opt(&value, "parent")?,
             priority: crate::serde::json_de_kv_opt(&value, "priority")?,
             reporter: crate::serde::json_de_kv_opt(&value, "reporter")?,
             resolution: crate::serde::json_de_kv_opt(&value, "resolution")?,
             resolutiondate: crate::serde::json_de_kv_opt(&value, "resolutiondate")?,
             status: crate::serde::json_de_kv_opt(&value, "status")?,
+            subtasks: crate::serde::json_de_kv_opt(&value, "subtasks")?,
             summary: crate::serde::json_de_kv(&value, "summary")?,
             updated: crate::serde::json_de_kv_opt(&value, "updated")?,
             votes: crate::serde::json_de_kv(&value, "votes")?,
@@ -142,4 +160,23 @@ impl IssueBean {
             versioned_representations: value.versioned_representations,
         })
     }
+
+    /// Value of a field decoded by its schema, or by the value shape if the schema isn't known
+    pub fn field_value(
+        &self,
+        id: &str,
+        dates: &crate::jira::DateDisplay,
+    ) -> Option<serde_json::Value> {
+        let value = self.fields.get(id)?;
+        self.field_type(id, &value).decode(&value, dates)
+    }
+
+    /// Type of a field by its schema, or by the value shape if the schema isn't known
+    pub fn field_type(&self, id: &str, value: &serde_json::Value) -> crate::jira::FieldType {
+        self.schema
+            .as_ref()
+            .and_then(|v| v.get(id))
+            .and_then(crate::jira::FieldType::of_schema)
+            .unwrap_or_else(|| crate::jira::FieldType::of_value(value))
+    }
 }
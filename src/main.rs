@@ -1,25 +1,38 @@
 mod authentication;
 mod config;
+mod config_check;
 mod confluence;
 mod confluence_content_get;
 mod confluence_content_update;
 mod confluence_types;
+mod error;
+mod http;
 mod jira;
 mod jira_types;
 mod printer;
 mod report;
+mod report_burndown;
 mod report_confluence_roadmap;
+mod report_critical_path;
+mod report_cycles;
 mod report_data;
 mod report_dependency_graph;
+mod report_gantt;
+mod report_hierarchy;
+mod report_schedule;
 mod report_storypoints;
+mod report_table;
 mod report_worklog;
 mod serde;
 
 extern crate slog_scope;
 
-use std::{io::Read, sync::Arc};
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, format_err, Context, Result};
 use clap::{Args, CommandFactory, Parser, Subcommand};
 
 const APP_CONFIG: &str = "~/.config/uprava.yaml";
@@ -33,12 +46,9 @@ struct CmdJiraGetIssue {
 
 impl CmdJiraGetIssue {
     pub async fn run(&self, config: crate::config::Config) -> Result<()> {
-        let issue = config
-            .default_jira_instance
-            .issue_bean(&self.issue)
-            .await
-            .unwrap();
-        println!("{}", self.format.data_to_string(&issue).unwrap());
+        let jira = &config.default_jira_instance;
+        let issue = jira.issue_bean(&self.issue).await?;
+        println!("{}", self.format.data_to_string(jira, &issue)?);
         Ok(())
     }
 }
@@ -88,19 +98,100 @@ impl CmdJiraGet {
 
 #[derive(Args, Debug)]
 struct CmdJiraSearch {
-    #[clap(short)]
-    format: crate::printer::SerdePrinter,
+    #[clap(short, required_unless_present = "columns")]
+    format: Option<crate::printer::SerdePrinter>,
+    /// Print a table of issue keys and the fields instead, e.g. `summary,story_points`. Field
+    /// aliases and field IDs are accepted
+    #[clap(long, value_delimiter = ',')]
+    columns: Vec<String>,
     query: String,
 }
 
 impl CmdJiraSearch {
     pub async fn run(&self, config: crate::config::Config) -> Result<()> {
-        let list = config
-            .default_jira_instance
+        let jira = &config.default_jira_instance;
+        let list = jira
             .search(&jira::SearchGetParams::new(&self.query))
-            .await
-            .unwrap();
-        println!("{}", self.format.data_to_string(&list).unwrap());
+            .await?;
+        let format = match &self.format {
+            Some(v) if self.columns.is_empty() => v,
+            _ => {
+                let mut rows = Vec::new();
+                for issue in list.issues.unwrap_or_default() {
+                    let issue = crate::jira_types::IssueBean::of_json(issue)?;
+                    let mut row = vec![issue.key.clone()];
+                    for column in &self.columns {
+                        let value = match jira.field_alias(column) {
+                            Some(alias) => alias.value(&issue, &jira.dates),
+                            None => issue.field_value(column, &jira.dates),
+                        };
+                        row.push(
+                            value
+                                .map(|v| crate::jira::field_text(&v))
+                                .unwrap_or_default(),
+                        );
+                    }
+                    rows.push(row);
+                }
+                let mut header = vec!["KEY".to_owned()];
+                header.extend(self.columns.iter().map(|v| v.to_uppercase()));
+                let header: Vec<_> = header.iter().map(|v| v.as_str()).collect();
+                print_table(&header, &rows);
+                return Ok(());
+            }
+        };
+        println!("{}", format.data_to_string(&list)?);
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+struct CmdJiraFields {
+    /// Show only fields with the text in ID or name, case insensitive
+    #[clap(long)]
+    search: Option<String>,
+    /// Name of Jira instance from `jira_instances`. Default instance is used if not set
+    #[clap(long)]
+    jira: Option<String>,
+}
+
+impl CmdJiraFields {
+    pub async fn run(&self, config: crate::config::Config) -> Result<()> {
+        let search = self.search.as_ref().map(|v| v.to_lowercase());
+        let mut fields = config.jira_instance(self.jira.as_deref())?.fields().await?;
+        fields.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+
+        let rows: Vec<_> = fields
+            .into_iter()
+            .filter(|v| match &search {
+                None => true,
+                Some(search) => [&v.id, &v.name].iter().any(|v| {
+                    v.as_deref()
+                        .unwrap_or_default()
+                        .to_lowercase()
+                        .contains(search)
+                }),
+            })
+            .map(|v| {
+                let (kind, schema) = match v.schema {
+                    None => (String::new(), String::new()),
+                    Some(schema) => (
+                        match schema.items {
+                            Some(items) => format!("{}<{}>", schema.type_, items),
+                            None => schema.type_,
+                        },
+                        schema.custom.or(schema.system).unwrap_or_default(),
+                    ),
+                };
+                [
+                    v.id.unwrap_or_default(),
+                    v.name.unwrap_or_default(),
+                    kind,
+                    schema,
+                ]
+            })
+            .collect();
+        print_table(&["ID", "NAME", "TYPE", "SCHEMA"], &rows);
         Ok(())
     }
 }
@@ -110,6 +201,8 @@ enum CmdJira {
     #[clap(subcommand)]
     Get(CmdJiraGet),
     Search(CmdJiraSearch),
+    /// List fields of Jira instance, their IDs are used in `custom_fields` of config file
+    Fields(CmdJiraFields),
 }
 
 impl CmdJira {
@@ -117,6 +210,7 @@ impl CmdJira {
         match self {
             CmdJira::Get(v) => v.run(config).await,
             CmdJira::Search(v) => v.run(config).await,
+            CmdJira::Fields(v) => v.run(config).await,
         }
     }
 }
@@ -131,18 +225,12 @@ struct CmdConfluenceGetContent {
 
 impl CmdConfluenceGetContent {
     pub async fn run(&self, config: crate::config::Config) -> Result<()> {
-        let content = config
+        let result = config
             .default_confluence_instance
-            .get_content(&self.space, &self.title)
-            .await
-            .unwrap();
-
-        let result = match content.results.first() {
-            None => bail!("No results found"),
-            Some(v) => v,
-        };
+            .get_page(&self.space, &self.title)
+            .await?;
 
-        println!("{}", self.format.data_to_string(result).unwrap());
+        println!("{}", self.format.data_to_string(&result)?);
 
         Ok(())
     }
@@ -199,16 +287,10 @@ struct CmdConfluenceUpdateWiki {
 
 impl CmdConfluenceUpdateWiki {
     pub async fn run(&self, config: crate::config::Config) -> Result<()> {
-        let get_result = config
+        let current_content = config
             .default_confluence_instance
-            .get_content(&self.space, &self.title)
-            .await
-            .unwrap();
-
-        let current_content = match get_result.results.first() {
-            None => bail!("No results found"),
-            Some(v) => v,
-        };
+            .get_page(&self.space, &self.title)
+            .await?;
 
         let id: u64 = current_content.id.parse()?;
 
@@ -242,24 +324,17 @@ impl CmdConfluenceUpdateWiki {
 
 impl CmdConfluenceUploadFile {
     pub async fn run(&self, config: crate::config::Config) -> Result<()> {
-        let get_result = config
+        let current_content = config
             .default_confluence_instance
-            .get_content(&self.space, &self.title)
-            .await
-            .unwrap();
-
-        let current_content = match get_result.results.first() {
-            None => bail!("No results found"),
-            Some(v) => v,
-        };
+            .get_page(&self.space, &self.title)
+            .await?;
 
         let id: u64 = current_content.id.parse()?;
 
         let _result = config
             .default_confluence_instance
             .upload_attachment(id, &self.path, &self.filename)
-            .await
-            .unwrap();
+            .await?;
 
         Ok(())
     }
@@ -294,20 +369,199 @@ impl CmdConfluence {
 #[derive(Args, Debug)]
 struct CmdReportMake {
     report: String,
+    /// Fail if dependencies of the report have cycles
+    #[clap(long)]
+    strict: bool,
 }
 
 impl CmdReportMake {
     pub async fn run(&self, config: Arc<crate::config::Config>) -> Result<()> {
         let report = match config.reports.get(&self.report) {
-            None => bail!("Report {:?} is not defined in config file", self.report),
+            None => {
+                return Err(crate::error::Error::new(
+                    crate::error::ErrorKind::NotFound,
+                    format!("Report {:?} is not defined in config file", self.report),
+                )
+                .into())
+            }
             Some(v) => v.clone(),
         };
-        match &report.0 {
-            report::Report::ConfluenceRoadmap(v) => v.make(config).await?,
-            report::Report::Worklog(v) => v.make(config).await?,
-            report::Report::StoryPoints(v) => v.make(config).await?,
+        let result = match &report.report {
+            report::Report::ConfluenceRoadmap(v) => v.make(config, self.strict).await,
+            report::Report::Worklog(v) => v.make(config).await,
+            report::Report::StoryPoints(v) => v.make(config).await,
+            report::Report::Table(v) => v.make(config).await,
+            report::Report::Burndown(v) => v.make(config).await,
+            report::Report::Gantt(v) => v.make(config).await,
+        };
+
+        result.with_context(|| format!("Report {:?} failed", self.report))
+    }
+}
+
+/// Prints rows as columns aligned by the widest cell
+fn print_table<R: AsRef<[String]>>(header: &[&str], rows: &[R]) {
+    let header: Vec<_> = header.iter().map(|v| v.to_string()).collect();
+    let mut widths: Vec<_> = header.iter().map(|v| v.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row.as_ref()) {
+            *width = (*width).max(cell.chars().count())
         }
+    }
+
+    for row in std::iter::once(header.as_slice()).chain(rows.iter().map(|v| v.as_ref())) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
 
+/// Issues found by JQL query with related issues up to `depth` and hierarchy up to
+/// `hierarchy_depth`
+async fn query_report_data(
+    config: Arc<crate::config::Config>,
+    jira: Option<&str>,
+    query: &str,
+    depth: usize,
+    hierarchy_depth: usize,
+) -> Result<crate::report_data::ReportData> {
+    let query_set = report::QuerySet::new(vec![crate::config::JiraQuery {
+        jira: config.jira_instance(jira)?.clone(),
+        query: query.to_owned(),
+    }]);
+    let issues_list = query_set.get_issues(config.clone()).await?;
+    crate::report_data::ReportData::of_slice(
+        &config.foreign_relations,
+        &issues_list,
+        depth,
+        hierarchy_depth,
+    )
+    .await
+}
+
+struct ReportRunResult {
+    name: String,
+    duration: std::time::Duration,
+    result: Result<()>,
+}
+
+#[derive(Args, Debug)]
+struct CmdReportMakeAll {
+    /// Maximum number of reports generated simultaneously
+    #[clap(short, long, default_value_t = 4)]
+    jobs: usize,
+    /// Run only reports with names matching any of the glob patterns
+    #[clap(long)]
+    only: Vec<glob::Pattern>,
+    /// Skip reports with names matching any of the glob patterns
+    #[clap(long)]
+    except: Vec<glob::Pattern>,
+    /// Run only reports having any of the tags
+    #[clap(long)]
+    tag: Vec<String>,
+    /// Fail reports whose dependencies have cycles
+    #[clap(long)]
+    strict: bool,
+}
+
+impl CmdReportMakeAll {
+    fn is_selected(&self, name: &str, report: &crate::config::Report) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|pattern| pattern.matches(name)))
+            && !self.except.iter().any(|pattern| pattern.matches(name))
+            && (self.tag.is_empty() || self.tag.iter().any(|tag| report.tags.contains(tag)))
+    }
+
+    fn print_summary(results: &[ReportRunResult]) {
+        let rows: Vec<_> = results
+            .iter()
+            .map(|v| {
+                let (status, error) = match &v.result {
+                    Ok(()) => ("ok", String::new()),
+                    Err(err) => ("failed", format!("{:#}", err)),
+                };
+                [
+                    v.name.clone(),
+                    status.to_owned(),
+                    format!("{:.1}s", v.duration.as_secs_f64()),
+                    error,
+                ]
+            })
+            .collect();
+
+        print_table(&["REPORT", "STATUS", "DURATION", "ERROR"], &rows);
+    }
+
+    pub async fn run(&self, config: Arc<crate::config::Config>) -> Result<()> {
+        let mut names: Vec<_> = config
+            .reports
+            .iter()
+            .filter(|(name, report)| self.is_selected(name, report))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(self.jobs.max(1)));
+        let mut tasks = Vec::new();
+        for name in names {
+            let config = config.clone();
+            let semaphore = semaphore.clone();
+            let strict = self.strict;
+            let report_name = name.clone();
+            let task = tokio::spawn(async move {
+                let (duration, result) = match semaphore.acquire_owned().await {
+                    Err(err) => (std::time::Duration::default(), Err(err.into())),
+                    Ok(_permit) => {
+                        slog_scope::info!("Running report {:?}", name);
+                        let started = std::time::Instant::now();
+                        let report = CmdReportMake {
+                            report: name.clone(),
+                            strict,
+                        };
+                        // Report runs in its own task, so its panic fails only this report
+                        let result =
+                            match tokio::spawn(async move { report.run(config).await }).await {
+                                Ok(v) => v,
+                                Err(err) => Err(format_err!("Report task failed: {}", err)),
+                            };
+                        (started.elapsed(), result)
+                    }
+                };
+                if let Err(err) = &result {
+                    slog_scope::error!("Report {:?} failed: {:#}", name, err);
+                }
+                ReportRunResult {
+                    name,
+                    duration,
+                    result,
+                }
+            });
+            tasks.push((report_name, task));
+        }
+
+        // Tasks are awaited in the order of names, so the summary is stable
+        let mut results = Vec::new();
+        for (name, task) in tasks {
+            let result = task.await.unwrap_or_else(|err| {
+                slog_scope::error!("Report {:?} failed: {}", name, err);
+                ReportRunResult {
+                    name,
+                    duration: std::time::Duration::default(),
+                    result: Err(format_err!("Report task failed: {}", err)),
+                }
+            });
+            results.push(result);
+        }
+
+        Self::print_summary(&results);
+
+        let failed = results.iter().filter(|v| v.result.is_err()).count();
+        if failed > 0 {
+            bail!("{} of {} reports failed", failed, results.len())
+        }
         Ok(())
     }
 }
@@ -315,7 +569,7 @@ impl CmdReportMake {
 #[derive(Subcommand, Debug)]
 enum CmdReport {
     Make(CmdReportMake),
-    MakeAll,
+    MakeAll(CmdReportMakeAll),
     List,
 }
 
@@ -324,16 +578,7 @@ impl CmdReport {
         let config = Arc::new(config);
         match self {
             CmdReport::Make(v) => v.run(config).await,
-            CmdReport::MakeAll => {
-                for (name, _) in config.reports.iter() {
-                    slog_scope::info!("Running report {:?}", name);
-                    let report = CmdReportMake {
-                        report: name.clone(),
-                    };
-                    report.run(config.clone()).await?;
-                }
-                Ok(())
-            }
+            CmdReport::MakeAll(v) => v.run(config).await,
             CmdReport::List => {
                 let mut names: Vec<_> = config.reports.keys().collect();
                 names.sort();
@@ -346,6 +591,279 @@ impl CmdReport {
     }
 }
 
+#[derive(Args, Debug)]
+struct CmdGraph {
+    /// JQL query selecting issues of the graph
+    query: String,
+    /// Name of Jira instance from `jira_instances`. Default instance is used if not set
+    #[clap(long)]
+    jira: Option<String>,
+    /// Recursion depth for related issues
+    #[clap(long, default_value_t = 1)]
+    depth: usize,
+    /// Recursion depth for sub-tasks, parents and epic hierarchy
+    #[clap(long, default_value_t = 0)]
+    hierarchy_depth: usize,
+    /// Output format. Guessed from the output file extension if not set
+    #[clap(short, long)]
+    format: Option<crate::report_dependency_graph::GraphFormat>,
+    /// Output file, "-" for STDOUT
+    #[clap(short, long)]
+    output: std::path::PathBuf,
+    /// Highlight critical path through dependencies
+    #[clap(long)]
+    critical_path: bool,
+    /// Fail if dependencies have cycles
+    #[clap(long)]
+    strict: bool,
+    /// Hide issues with status of "done" category
+    #[clap(long)]
+    hide_done: bool,
+    /// Draw only relations of given kinds, comma separated
+    #[clap(long, value_delimiter = ',')]
+    kinds: Vec<String>,
+    /// Draw each epic as a single node with relations of its issues
+    #[clap(long)]
+    collapse_epics: bool,
+    /// Draw only given issues and their neighbours, comma separated keys
+    #[clap(long, value_delimiter = ',')]
+    around: Vec<String>,
+    /// Number of relations between `--around` issues and drawn neighbours
+    #[clap(long, default_value_t = 1)]
+    around_depth: usize,
+    /// Cluster issues by "epic", "jira", "assignee" or "none"
+    #[clap(long, default_value = "epic")]
+    cluster_by: crate::report_dependency_graph::GraphClusterBy,
+}
+
+impl CmdGraph {
+    pub async fn run(&self, config: crate::config::Config) -> Result<()> {
+        let format = match self.format {
+            Some(v) => v,
+            None => self
+                .output
+                .extension()
+                .and_then(|v| v.to_str())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+        };
+
+        let data = query_report_data(
+            Arc::new(config),
+            self.jira.as_deref(),
+            &self.query,
+            self.depth,
+            self.hierarchy_depth,
+        )
+        .await?;
+
+        let cycles = crate::report_cycles::Cycles::find(&data);
+        cycles.warn();
+        if self.strict {
+            cycles.check()?;
+        }
+
+        let highlights = if self.critical_path {
+            let result = crate::report_critical_path::CriticalPath::default().calculate(&data);
+            crate::report_dependency_graph::GraphHighlights::of_critical_path(&result)
+        } else {
+            crate::report_dependency_graph::GraphHighlights::default()
+        }
+        .with_cycles(&cycles)
+        .with_schedule(&crate::report_schedule::ScheduleCheck::find(&data));
+
+        let graph = crate::report_dependency_graph::DependencyGraph {
+            format,
+            hide_done: self.hide_done,
+            relation_kinds: self.kinds.clone(),
+            collapse_epics: self.collapse_epics,
+            neighbourhood: self.around.clone(),
+            neighbourhood_depth: self.around_depth,
+            cluster_by: self.cluster_by,
+        };
+        let content = graph.render(&data, &highlights)?;
+        if self.output.as_os_str() == "-" {
+            std::io::stdout().write_all(&content)?;
+        } else {
+            std::fs::write(&self.output, content).map_err(|err| {
+                anyhow::format_err!("Failed to write graph to {:?}: {}", self.output, err)
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+struct CmdGantt {
+    /// JQL query selecting issues of the chart
+    query: String,
+    /// Name of Jira instance from `jira_instances`. Default instance is used if not set
+    #[clap(long)]
+    jira: Option<String>,
+    /// Recursion depth for dependencies drawn on the chart
+    #[clap(long, default_value_t = 1)]
+    depth: usize,
+    /// Group bars by "epic" or "assignee"
+    #[clap(long, default_value = "epic")]
+    group_by: crate::report_gantt::GanttGroupBy,
+    /// Output format. Guessed from the output file extension if not set
+    #[clap(short, long)]
+    format: Option<crate::report_gantt::GanttFormat>,
+    /// Output file, "-" for STDOUT
+    #[clap(short, long)]
+    output: std::path::PathBuf,
+}
+
+impl CmdGantt {
+    pub async fn run(&self, config: crate::config::Config) -> Result<()> {
+        let format = match self.format {
+            Some(v) => v,
+            None => self
+                .output
+                .extension()
+                .and_then(|v| v.to_str())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default(),
+        };
+
+        let data = query_report_data(
+            Arc::new(config),
+            self.jira.as_deref(),
+            &self.query,
+            self.depth,
+            0,
+        )
+        .await?;
+
+        let chart = crate::report_gantt::GanttChart {
+            group_by: self.group_by,
+            format,
+        };
+        let content = chart.render(&data)?;
+        if self.output.as_os_str() == "-" {
+            std::io::stdout().write_all(&content)?;
+        } else {
+            std::fs::write(&self.output, content).map_err(|err| {
+                anyhow::format_err!("Failed to write Gantt chart to {:?}: {}", self.output, err)
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+struct CmdCheckSchedule {
+    /// JQL query selecting issues to check
+    query: String,
+    /// Name of Jira instance from `jira_instances`. Default instance is used if not set
+    #[clap(long)]
+    jira: Option<String>,
+    /// Recursion depth for related issues
+    #[clap(long, default_value_t = 1)]
+    depth: usize,
+}
+
+impl CmdCheckSchedule {
+    pub async fn run(&self, config: crate::config::Config) -> Result<()> {
+        let data = query_report_data(
+            Arc::new(config),
+            self.jira.as_deref(),
+            &self.query,
+            self.depth,
+            0,
+        )
+        .await?;
+
+        let schedule = crate::report_schedule::ScheduleCheck::find(&data);
+        let rows: Vec<_> = schedule
+            .problems
+            .iter()
+            .map(|v| {
+                [
+                    v.issue.issue.clone(),
+                    v.dependency
+                        .as_ref()
+                        .map(|v| v.issue.clone())
+                        .unwrap_or_default(),
+                    v.as_string(&data),
+                ]
+            })
+            .collect();
+        print_table(&["ISSUE", "DEPENDENCY", "PROBLEM"], &rows);
+
+        if !schedule.problems.is_empty() {
+            bail!("{} schedule problems found", schedule.problems.len())
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum CmdCheck {
+    /// Check planned dates of issues against their dependencies
+    Schedule(CmdCheckSchedule),
+}
+
+impl CmdCheck {
+    pub async fn run(&self, config: crate::config::Config) -> Result<()> {
+        match self {
+            CmdCheck::Schedule(v) => v.run(config).await,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct CmdConfigCheck {
+    /// Only check the structure of config file, without requests to servers
+    #[clap(long)]
+    offline: bool,
+}
+
+impl CmdConfigCheck {
+    pub async fn run(&self, config: crate::config::Config) -> Result<()> {
+        let check = crate::config_check::ConfigCheck::run(&config, self.offline).await;
+        let rows: Vec<_> = check
+            .results
+            .iter()
+            .map(|v| {
+                [
+                    v.check.to_owned(),
+                    v.subject.clone(),
+                    v.status.to_string(),
+                    v.details.clone(),
+                ]
+            })
+            .collect();
+        print_table(&["CHECK", "SUBJECT", "RESULT", "DETAILS"], &rows);
+
+        let failed = check.failed();
+        if failed > 0 {
+            return Err(crate::error::Error::new(
+                crate::error::ErrorKind::Validation,
+                format!("{} config checks failed", failed),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum CmdConfig {
+    /// Validate config file, access to instances, custom fields, report queries and pages
+    Check(CmdConfigCheck),
+}
+
+impl CmdConfig {
+    pub async fn run(&self, config: crate::config::Config) -> Result<()> {
+        match self {
+            CmdConfig::Check(v) => v.run(config).await,
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum CmdApplication {
     #[clap(subcommand)]
@@ -354,6 +872,14 @@ enum CmdApplication {
     Confluence(CmdConfluence),
     #[clap(subcommand)]
     Report(CmdReport),
+    /// Build dependency graph of issues found by JQL query
+    Graph(CmdGraph),
+    /// Build Gantt chart of issues found by JQL query
+    Gantt(CmdGantt),
+    #[clap(subcommand)]
+    Check(CmdCheck),
+    #[clap(subcommand)]
+    Config(CmdConfig),
     Completions {
         shell: clap_complete_command::Shell,
     },
@@ -364,9 +890,12 @@ enum CmdApplication {
 struct Application {
     #[clap(subcommand)]
     command: CmdApplication,
-    #[clap(short, default_value = APP_CONFIG)]
+    #[clap(short, default_value = APP_CONFIG, env = "UPRAVA_CONFIG")]
     /// Path to configuration file
     pub config: String,
+    #[clap(long, env = "UPRAVA_PROFILE")]
+    /// Profile of configuration file applied on top of it
+    pub profile: Option<String>,
 }
 
 impl Application {
@@ -375,6 +904,10 @@ impl Application {
             CmdApplication::Jira(v) => v.run(config).await,
             CmdApplication::Confluence(v) => v.run(config).await,
             CmdApplication::Report(v) => v.run(config).await,
+            CmdApplication::Graph(v) => v.run(config).await,
+            CmdApplication::Gantt(v) => v.run(config).await,
+            CmdApplication::Check(v) => v.run(config).await,
+            CmdApplication::Config(v) => v.run(config).await,
             CmdApplication::Completions { shell } => {
                 shell.generate(&mut Application::command(), &mut std::io::stdout());
                 Ok(())
@@ -382,17 +915,61 @@ impl Application {
         }
     }
 
-    pub fn run(&self) {
-        let _logger_guard = slog_envlogger::init().unwrap();
+    /// Commands using Jira need custom fields resolved by display names. Config check resolves
+    /// them itself to report every unknown field
+    fn uses_jira(&self) -> bool {
+        !matches!(
+            &self.command,
+            CmdApplication::Confluence(_)
+                | CmdApplication::Report(CmdReport::List)
+                | CmdApplication::Config(CmdConfig::Check(_))
+                | CmdApplication::Completions { .. }
+        )
+    }
 
+    fn try_run(&self) -> Result<()> {
         let config_path = shellexpand::tilde(&self.config).to_string();
-        let config = crate::config::Config::read(&config_path).expect("Config");
+        let rt = tokio::runtime::Runtime::new()
+            .map_err(|err| anyhow::format_err!("Failed to start async runtime: {}", err))?;
+        rt.block_on(async {
+            let config = crate::config::Config::read(
+                &config_path,
+                self.profile.as_deref(),
+                self.uses_jira(),
+            )
+            .await
+            // Errors of requests resolving custom fields keep their kinds
+            .map_err(|err| match crate::error::ErrorKind::of_error(&err) {
+                crate::error::ErrorKind::Other => crate::error::Error::new(
+                    crate::error::ErrorKind::Validation,
+                    format!("{:#}", err),
+                )
+                .into(),
+                _ => err,
+            })?;
+            self.run_command(config).await
+        })
+    }
 
-        let rt = tokio::runtime::Runtime::new().expect("Async runtime");
-        rt.block_on(self.run_command(config)).expect("Runtime")
+    pub fn run(&self) -> std::process::ExitCode {
+        let _logger_guard = match slog_envlogger::init() {
+            Ok(v) => v,
+            Err(err) => {
+                eprintln!("Error: failed to initialize logger: {}", err);
+                return std::process::ExitCode::FAILURE;
+            }
+        };
+
+        match self.try_run() {
+            Ok(()) => std::process::ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("Error: {:#}", err);
+                std::process::ExitCode::from(crate::error::ErrorKind::of_error(&err).exit_code())
+            }
+        }
     }
 }
 
-fn main() {
+fn main() -> std::process::ExitCode {
     Application::parse().run()
 }
@@ -3,9 +3,41 @@ use std::sync::Arc;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone)]
-pub enum ReportResult {
-    ConfluenceRoadmap(crate::report_confluence_roadmap::ConfluenceRoadmap),
+/// Typed value of a report column, so keys, numbers and dates are sorted by their meaning rather
+/// than by rendered text. Values of different types are ordered by the variant
+#[derive(PartialEq, PartialOrd, Debug)]
+pub enum SortValue {
+    Empty,
+    Number(f64),
+    Moment(chrono::DateTime<chrono::Utc>),
+    /// Issue key split into project and number, so `PROJ-9` goes before `PROJ-10`
+    Key(String, u64),
+    Text(String),
+}
+
+impl SortValue {
+    /// Issue key if the text is one, otherwise the text itself
+    pub fn of_text(text: &str) -> Self {
+        let key = text.rsplit_once('-').and_then(|(project, number)| {
+            let is_project = project.starts_with(|c: char| c.is_ascii_uppercase())
+                && project
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+            match (is_project, number.parse::<u64>()) {
+                (true, Ok(number)) => Some(Self::Key(project.to_owned(), number)),
+                _ => None,
+            }
+        });
+        match key {
+            Some(v) => v,
+            None if text.is_empty() => Self::Empty,
+            None => Self::Text(text.to_owned()),
+        }
+    }
+
+    pub fn compare(&self, other: &Self) -> std::cmp::Ordering {
+        self.partial_cmp(other).unwrap_or(std::cmp::Ordering::Equal)
+    }
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -13,6 +45,8 @@ pub enum ReportIssueType {
     ReportMember,
     ExternalDependency,
     Epic,
+    /// Parent or child of other issues fetched by hierarchy fields
+    Hierarchy,
 }
 
 #[derive(Clone)]
@@ -38,10 +72,9 @@ impl ReportIssue {
         })
     }
 
+    /// Issue page, the context path of the instance is kept
     pub fn url(&self) -> url::Url {
-        let mut url = self.jira.base_url.clone();
-        url.set_path(&format!("browse/{}", self.issue.key));
-        url
+        crate::http::api_url(&self.jira.base_url, &format!("browse/{}", self.issue.key))
     }
 
     pub fn confluence_wiki_url(&self, newlines: bool) -> String {
@@ -73,38 +106,90 @@ impl ReportIssue {
     }
 
     pub fn confluence_wiki_schedule(&self) -> String {
-        let duration = self.custom_fields.plan();
-        let planned_end = self
-            .custom_fields
-            .planned_end
-            .unwrap_or_else(|| chrono::Utc::now() + chrono::Duration::days(100000));
-        let planned_start = self
-            .custom_fields
-            .planned_start
-            .unwrap_or_else(|| chrono::Utc::now() + chrono::Duration::days(100000));
-        if planned_end - chrono::Duration::days(3) < chrono::Utc::now() {
-            format!("{{color:red}}{}{{color}}", duration)
-        } else if planned_start - chrono::Duration::days(3) < chrono::Utc::now() {
-            format!("{{color:green}}{}{{color}}", duration)
-        } else {
-            duration
+        let duration = self.custom_fields.plan(&self.jira.dates);
+        match self.custom_fields.plan_color(&self.jira.dates) {
+            None => duration,
+            Some(color) => format!("{{color:{}}}{}{{color}}", color, duration),
         }
     }
 
-    pub fn custom_field_f64(&self, field: &str) -> Option<f64> {
+    /// Moment of resolution, `None` for unresolved issues
+    pub fn resolved_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
         self.issue
             .fields
-            .custom_fields
-            .get(field)
-            .and_then(|f| f.as_f64())
+            .resolutiondate
+            .as_deref()
+            .and_then(crate::jira::parse_jira_datetime)
+    }
+
+    pub fn custom_field_f64(&self, field: &str) -> Option<f64> {
+        let value = self.issue.fields.custom_fields.get(field)?;
+        crate::jira::FieldType::Number
+            .decode(value, &self.jira.dates)
+            .and_then(|v| v.as_f64())
     }
 
     pub fn custom_field_str(&self, field: &str) -> Option<String> {
         self.issue
-            .fields
-            .custom_fields
-            .get(field)
-            .map(|f| f.to_string())
+            .field_value(field, &self.jira.dates)
+            .map(|v| crate::jira::field_text(&v))
+    }
+
+    /// Typed value of the field alias, or of the custom field if there is no such alias
+    pub fn sort_value(&self, field: &str) -> SortValue {
+        use crate::jira::FieldType;
+
+        let (id, kind) = match self.jira.field_alias(field) {
+            Some(alias) => (alias.field.name.as_str(), alias.kind),
+            None => (field, None),
+        };
+        let value = match self.issue.fields.get(id) {
+            None | Some(serde_json::Value::Null) => return SortValue::Empty,
+            Some(v) => v,
+        };
+        let dates = &self.jira.dates;
+        let kind = kind.unwrap_or_else(|| self.issue.field_type(id, &value));
+        let r = match kind {
+            FieldType::Number => FieldType::Number
+                .decode(&value, dates)
+                .and_then(|v| v.as_f64())
+                .map(SortValue::Number),
+            FieldType::Date | FieldType::DateTime => value
+                .as_str()
+                .and_then(|v| dates.parse(v))
+                .map(SortValue::Moment),
+            kind => kind
+                .decode(&value, dates)
+                .map(|v| SortValue::of_text(&crate::jira::field_text(&v))),
+        };
+        r.unwrap_or(SortValue::Empty)
+    }
+
+    /// Decoded value of the field alias of the issue's instance
+    pub fn field_value(&self, alias: &str) -> Option<serde_json::Value> {
+        self.jira
+            .field_alias(alias)?
+            .value(&self.issue, &self.jira.dates)
+    }
+
+    /// Value of the field alias, or of the custom field if there is no such alias
+    pub fn field_str(&self, field: &str) -> Option<String> {
+        match self.jira.field_alias(field) {
+            None => self.custom_field_str(field),
+            Some(alias) => alias
+                .value(&self.issue, &self.jira.dates)
+                .map(|v| crate::jira::field_text(&v)),
+        }
+    }
+
+    /// Numeric value of the field alias, or of the custom field if there is no such alias
+    pub fn field_f64(&self, field: &str) -> Option<f64> {
+        match self.jira.field_alias(field) {
+            None => self.custom_field_f64(field),
+            Some(alias) => alias
+                .value(&self.issue, &self.jira.dates)
+                .and_then(|v| v.as_f64()),
+        }
     }
 }
 
@@ -115,11 +200,102 @@ pub struct ForeignRelationSubject {
     pub kind: String,
 }
 
-#[derive(Hash, PartialEq, Eq, Serialize, Deserialize, Clone)]
-pub enum RelationKind {
-    Dependance,
-    Block,
-    Mention,
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Hash, PartialEq, Eq, Debug)]
+pub enum RelationDirection {
+    /// Drawn as an arrow from source to target
+    #[default]
+    Directed,
+    /// Drawn as a line without arrows
+    Undirected,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Hash, PartialEq, Eq, Debug)]
+pub enum LineStyle {
+    #[default]
+    Solid,
+    Bold,
+    Dashed,
+    Dotted,
+}
+
+#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+pub struct RelationStyle {
+    pub color: String,
+    #[serde(default)]
+    pub line: LineStyle,
+    /// Text drawn next to the edge
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+fn default_traverse() -> bool {
+    true
+}
+
+/// Kind of relation between issues recognized by names of Jira issue links
+#[derive(Serialize, Deserialize, Clone, Hash, PartialEq, Eq, Debug)]
+pub struct RelationKind {
+    pub name: String,
+    /// Link names which in "A <link name> B" mean relation from B to A
+    #[serde(default)]
+    pub inward: Vec<String>,
+    /// Link names which in "A <link name> B" mean relation from A to B
+    #[serde(default)]
+    pub outward: Vec<String>,
+    #[serde(default)]
+    pub direction: RelationDirection,
+    /// Fetch related issues following relations of this kind
+    #[serde(default = "default_traverse")]
+    pub traverse: bool,
+    /// Source of the relation should be finished before its target starts. Used by cycles
+    /// detection, critical path and schedule checks
+    #[serde(default)]
+    pub ordering: bool,
+    pub style: RelationStyle,
+}
+
+/// Dependance, blocking and mention relations of standard Jira link types
+pub fn default_relation_kinds() -> Vec<RelationKind> {
+    let kind =
+        |name: &str, inward: &[&str], outward: &[&str], ordering, color: &str, line| RelationKind {
+            name: name.to_owned(),
+            inward: inward.iter().map(|v| v.to_string()).collect(),
+            outward: outward.iter().map(|v| v.to_string()).collect(),
+            direction: RelationDirection::Directed,
+            traverse: true,
+            ordering,
+            style: RelationStyle {
+                color: color.to_owned(),
+                line,
+                label: None,
+            },
+        };
+    vec![
+        kind(
+            "Dependance",
+            &["depends on"],
+            &["dependance for"],
+            true,
+            "#2E56A6",
+            LineStyle::Solid,
+        ),
+        kind(
+            "Block",
+            &["is blocked by"],
+            &["blocks"],
+            true,
+            "#A65229",
+            LineStyle::Bold,
+        ),
+        kind(
+            "Mention",
+            &["mentions"],
+            &["mentioned in", "relates to"],
+            false,
+            "#7F94BF",
+            LineStyle::Dashed,
+        ),
+    ]
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -132,22 +308,39 @@ pub struct ForeignRelation {
 pub struct QuerySet(Vec<crate::config::JiraQuery>);
 
 impl QuerySet {
+    pub fn new(queries: Vec<crate::config::JiraQuery>) -> Self {
+        Self(queries)
+    }
+
+    pub fn queries(&self) -> &[crate::config::JiraQuery] {
+        &self.0
+    }
+
     pub async fn get_issues(&self, config: Arc<crate::config::Config>) -> Result<Vec<ReportIssue>> {
+        self.get_issues_expanded(config, &[]).await
+    }
+
+    /// Same as [`QuerySet::get_issues`], but requests additional issue details, e.g. `changelog`
+    pub async fn get_issues_expanded(
+        &self,
+        config: Arc<crate::config::Config>,
+        expand: &[&str],
+    ) -> Result<Vec<ReportIssue>> {
+        let expand: Vec<String> = expand.iter().map(|v| v.to_string()).collect();
         let mut issues_list = Vec::new();
         let mut join_set = tokio::task::JoinSet::new();
         for query in &self.0 {
             let query_clone = query.clone();
             let config = config.clone();
+            let expand = expand.clone();
             let _abort_handle = join_set.spawn(async move {
-                let mut query_string = query_clone.query.replace('\n', " ").trim().to_string();
-                for (subst_key, subst_value) in &config.substitutions {
-                    query_string = query_string.replace(&format!("%{subst_key}%"), subst_value);
-                }
+                let query_string = config.substitute(&query_clone.query);
                 slog_scope::info!("Querying JIRA: {}", query_string);
-                let handler = query_clone
-                    .jira
-                    .search_all(&crate::jira::SearchGetParams::new(&query_string))
-                    .await;
+                let mut params = crate::jira::SearchGetParams::new(&query_string);
+                if !expand.is_empty() {
+                    params.expand = Some(expand)
+                }
+                let handler = query_clone.jira.search_all(&params).await;
                 (handler, query_clone)
             });
         }
@@ -174,4 +367,94 @@ pub enum Report {
     Worklog(crate::report_worklog::Worklog),
     #[serde(with = "serde_yaml::with::singleton_map")]
     StoryPoints(crate::report_storypoints::StoryPoints),
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    Table(crate::report_table::Table),
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    Burndown(crate::report_burndown::Burndown),
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    Gantt(crate::report_gantt::Gantt),
+}
+
+/// Confluence page updated by a report
+pub struct ReportPage<'a> {
+    pub confluence: &'a crate::confluence::ConfluenceServer,
+    pub space: &'a str,
+    pub title: &'a str,
+}
+
+impl Report {
+    pub fn page(&self) -> ReportPage<'_> {
+        match self {
+            Report::ConfluenceRoadmap(v) => v.page(),
+            Report::Worklog(v) => v.page(),
+            Report::StoryPoints(v) => v.page(),
+            Report::Table(v) => v.page(),
+            Report::Burndown(v) => v.page(),
+            Report::Gantt(v) => v.page(),
+        }
+    }
+
+    pub fn queries(&self) -> Vec<&crate::config::JiraQuery> {
+        match self {
+            Report::ConfluenceRoadmap(v) => v.queries(),
+            Report::Worklog(v) => v.queries(),
+            Report::StoryPoints(v) => v.queries(),
+            Report::Table(v) => v.queries(),
+            Report::Burndown(v) => v.queries(),
+            Report::Gantt(v) => v.queries(),
+        }
+    }
+
+    pub fn foreign_relations(&self) -> &[ForeignRelation] {
+        match self {
+            Report::ConfluenceRoadmap(v) => v.foreign_relations(),
+            Report::Gantt(v) => v.foreign_relations(),
+            Report::Worklog(_)
+            | Report::StoryPoints(_)
+            | Report::Table(_)
+            | Report::Burndown(_) => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SortValue;
+
+    #[test]
+    fn sort_value_orders_keys_by_number() {
+        let mut values: Vec<_> = ["PROJ-10", "PROJ-9", "ABC-100", ""]
+            .iter()
+            .map(|v| SortValue::of_text(v))
+            .collect();
+        values.sort_by(|a, b| a.compare(b));
+        assert_eq!(
+            values,
+            vec![
+                SortValue::Empty,
+                SortValue::Key("ABC".to_owned(), 100),
+                SortValue::Key("PROJ".to_owned(), 9),
+                SortValue::Key("PROJ".to_owned(), 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_value_keeps_other_text() {
+        assert_eq!(
+            SortValue::of_text("In-Progress"),
+            SortValue::Text("In-Progress".to_owned())
+        );
+        assert!(SortValue::Number(9.0) < SortValue::Number(10.0));
+    }
+
+    #[test]
+    fn issue_url_keeps_context_path() {
+        let mut issue = crate::report_data::test_data::issue("T-1", None, None, None);
+        assert_eq!(issue.url().as_str(), "https://jira.test/browse/T-1");
+        for base_url in ["https://example.com/jira", "https://example.com/jira/"] {
+            issue.jira.base_url = url::Url::parse(base_url).unwrap();
+            assert_eq!(issue.url().as_str(), "https://example.com/jira/browse/T-1");
+        }
+    }
 }
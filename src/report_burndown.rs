@@ -0,0 +1,755 @@
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write, sync::Arc};
+
+use crate::jira::{parse_jira_datetime, DateDisplay};
+use crate::report::ReportIssue;
+
+fn default_scope_field() -> String {
+    "Sprint".to_owned()
+}
+
+/// Timeline of a single issue rebuilt from its changelog
+pub struct IssueHistory {
+    key: String,
+    summary: String,
+    created: chrono::DateTime<chrono::Utc>,
+    /// Sorted list of (moment, in scope) switches: additions to the scope, e.g. to the sprint,
+    /// and removals from it
+    scope: Vec<(chrono::DateTime<chrono::Utc>, bool)>,
+    /// Sorted list of (moment, resolved) switches
+    resolutions: Vec<(chrono::DateTime<chrono::Utc>, bool)>,
+    /// Sorted list of (moment, new value) estimation changes
+    estimations: Vec<(chrono::DateTime<chrono::Utc>, f64)>,
+    /// Estimation before the first change in the changelog
+    initial_estimation: f64,
+}
+
+/// Values of a changelog item, e.g. IDs and names of sprints
+fn item_values<'a>(id: &'a Option<String>, text: &'a Option<String>) -> Vec<&'a str> {
+    [id, text]
+        .into_iter()
+        .flatten()
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+impl IssueHistory {
+    fn of_issue(
+        issue: &ReportIssue,
+        story_points_field: &Option<String>,
+        scope_field: &str,
+        scope_value: Option<&str>,
+    ) -> Option<Self> {
+        let story_points_field = story_points_field.as_deref();
+        let field_id = story_points_field.map(|v| issue.jira.field_id(v));
+        let scope_field_id = issue.jira.field_id(scope_field);
+        let created = parse_jira_datetime(&issue.issue.fields.created)?;
+
+        let mut scope_changes = Vec::new();
+        let mut resolutions = Vec::new();
+        let mut estimations = Vec::new();
+        let mut first_estimation = None;
+
+        let histories = issue
+            .issue
+            .changelog
+            .as_ref()
+            .and_then(|v| v.histories.as_deref())
+            .unwrap_or_default();
+        for history in histories {
+            let moment = match history.created.as_deref().and_then(parse_jira_datetime) {
+                None => continue,
+                Some(v) => v,
+            };
+            for item in history.items.as_deref().unwrap_or_default() {
+                let item_field_id = item.field_id.as_deref().or(item.field.as_deref());
+                let is_scope_field = [item.field.as_deref(), item.field_id.as_deref()]
+                    .iter()
+                    .any(|v| *v == Some(scope_field) || *v == Some(scope_field_id));
+                if is_scope_field {
+                    let from = item_values(&item.from, &item.from_string);
+                    let to = item_values(&item.to, &item.to_string);
+                    // Moves between other values, e.g. to the next sprint, keep the issue in
+                    // the scope only without `scope_value`
+                    let added = match scope_value {
+                        Some(v) => {
+                            (from.contains(&v) != to.contains(&v)).then_some(to.contains(&v))
+                        }
+                        None => (from.is_empty() != to.is_empty()).then_some(!to.is_empty()),
+                    };
+                    if let Some(added) = added {
+                        scope_changes.push((moment, added))
+                    }
+                }
+                if item_field_id == Some("resolution") {
+                    resolutions.push((moment, item.to.is_some() || item.to_string.is_some()))
+                }
+                if field_id.is_some() && item_field_id == field_id {
+                    let parse = |v: &Option<String>| {
+                        v.as_deref()
+                            .and_then(|v| v.trim().parse::<f64>().ok())
+                            .unwrap_or_default()
+                    };
+                    if first_estimation.is_none() {
+                        first_estimation = Some(parse(&item.from_string))
+                    }
+                    estimations.push((moment, parse(&item.to_string)))
+                }
+            }
+        }
+
+        if resolutions.is_empty() {
+            if let Some(resolved) = issue
+                .issue
+                .fields
+                .resolutiondate
+                .as_deref()
+                .and_then(parse_jira_datetime)
+            {
+                resolutions.push((resolved, true))
+            }
+        }
+        resolutions.sort_by_key(|(moment, _)| *moment);
+        estimations.sort_by_key(|(moment, _)| *moment);
+        scope_changes.sort_by_key(|(moment, _)| *moment);
+
+        // Only the first of repeated additions or removals switches the scope
+        let mut scope: Vec<(chrono::DateTime<chrono::Utc>, bool)> = Vec::new();
+        for (moment, added) in scope_changes {
+            if scope.last().map(|(_, v)| *v) != Some(added) {
+                scope.push((moment, added))
+            }
+        }
+
+        let initial_estimation = match (story_points_field, first_estimation) {
+            (None, _) => 1.0,
+            (Some(_), Some(v)) => v,
+            (Some(field), None) => issue.field_f64(field).unwrap_or_default(),
+        };
+
+        Some(Self {
+            key: issue.issue.key.clone(),
+            summary: issue.issue.fields.summary.clone(),
+            created,
+            scope,
+            resolutions,
+            estimations,
+            initial_estimation,
+        })
+    }
+
+    /// Issue is created in the scope unless it is added to the scope later
+    fn is_created_in_scope(&self) -> bool {
+        self.scope.first().is_none_or(|(_, added)| !added)
+    }
+
+    fn is_in_scope(&self, moment: chrono::DateTime<chrono::Utc>) -> bool {
+        match self.scope.iter().take_while(|(v, _)| *v <= moment).last() {
+            Some((_, in_scope)) => *in_scope,
+            None => self.created <= moment && self.is_created_in_scope(),
+        }
+    }
+
+    /// Moments the issue entered the scope: its creation and additions
+    fn additions(&self) -> impl Iterator<Item = chrono::DateTime<chrono::Utc>> + '_ {
+        let created = self.is_created_in_scope().then_some(self.created);
+        created.into_iter().chain(
+            self.scope
+                .iter()
+                .filter(|(_, added)| *added)
+                .map(|(moment, _)| *moment),
+        )
+    }
+
+    fn is_resolved(&self, moment: chrono::DateTime<chrono::Utc>) -> bool {
+        self.resolutions
+            .iter()
+            .take_while(|(v, _)| *v <= moment)
+            .last()
+            .map(|(_, resolved)| *resolved)
+            .unwrap_or(false)
+    }
+
+    fn estimation(&self, moment: chrono::DateTime<chrono::Utc>) -> f64 {
+        self.estimations
+            .iter()
+            .take_while(|(v, _)| *v <= moment)
+            .last()
+            .map(|(_, estimation)| *estimation)
+            .unwrap_or(self.initial_estimation)
+    }
+}
+
+pub struct DayPoint {
+    date: NaiveDate,
+    scope: f64,
+    completed: f64,
+    /// Scope of issues created or moved into the scope during this day after the period start,
+    /// and estimation increases
+    added: f64,
+}
+
+type DayPointValue = fn(&DayPoint) -> f64;
+
+impl DayPoint {
+    fn remaining(&self) -> f64 {
+        self.scope - self.completed
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Burndown {
+    query_set: crate::report::QuerySet,
+    confluence: crate::confluence::ConfluenceServer,
+    space: String,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    /// First day of the period, e.g. `2022-10-03`
+    start: NaiveDate,
+    /// Last day of the period
+    end: NaiveDate,
+    /// Field alias or custom field with story points. Issues are counted when not set
+    #[serde(default)]
+    story_points_field: Option<String>,
+    /// Field which changes move an issue into the scope and out of it, `Sprint` by default
+    #[serde(default = "default_scope_field")]
+    scope_field: String,
+    /// Value of `scope_field` defining the scope, e.g. name or ID of the sprint. Without it an
+    /// issue is in the scope while the field is set
+    #[serde(default)]
+    scope_value: Option<String>,
+}
+
+impl Burndown {
+    /// Confluence page updated by the report
+    pub fn page(&self) -> crate::report::ReportPage<'_> {
+        crate::report::ReportPage {
+            confluence: &self.confluence,
+            space: &self.space,
+            title: &self.title,
+        }
+    }
+
+    /// Jira queries of the report
+    pub fn queries(&self) -> Vec<&crate::config::JiraQuery> {
+        self.query_set.queries().iter().collect()
+    }
+
+    /// Last moment of the date in the timezone
+    fn end_of_day(dates: &DateDisplay, date: NaiveDate) -> chrono::DateTime<chrono::Utc> {
+        date.succ_opt()
+            .and_then(|v| dates.start_of_day(v))
+            .map(|v| v - chrono::Duration::nanoseconds(1))
+            .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC)
+    }
+
+    fn calculate(&self, histories: &[IssueHistory], dates: &DateDisplay) -> Vec<DayPoint> {
+        let today = dates.today();
+        let period_start = Self::end_of_day(dates, self.start);
+
+        let mut points = Vec::new();
+        let mut date = self.start;
+        while date <= self.end && date <= today {
+            let moment = Self::end_of_day(dates, date);
+            let mut point = DayPoint {
+                date,
+                scope: 0.0,
+                completed: 0.0,
+                added: 0.0,
+            };
+            let previous = moment - chrono::Duration::days(1);
+            for history in histories.iter().filter(|v| v.is_in_scope(moment)) {
+                let estimation = history.estimation(moment);
+                point.scope += estimation;
+                if history.is_resolved(moment) {
+                    point.completed += estimation
+                }
+                let added = history
+                    .additions()
+                    .any(|v| v > period_start && dates.date(v) == date);
+                if added {
+                    point.added += estimation
+                } else if previous >= period_start && history.is_in_scope(previous) {
+                    point.added += (estimation - history.estimation(previous)).max(0.0)
+                }
+            }
+            points.push(point);
+            date = match date.succ_opt() {
+                None => break,
+                Some(v) => v,
+            };
+        }
+        points
+    }
+
+    fn unit(&self) -> &str {
+        match self.story_points_field {
+            None => "задачи",
+            Some(_) => "сторипоинты",
+        }
+    }
+
+    pub fn generate_svg(&self, points: &[DayPoint]) -> Result<String> {
+        const WIDTH: f64 = 900.0;
+        const HEIGHT: f64 = 400.0;
+        const MARGIN: f64 = 50.0;
+
+        let days = (self.end - self.start).num_days().max(1) as f64;
+        let max_value = points.iter().map(|v| v.scope).fold(1.0, f64::max).ceil();
+        let x = |date: NaiveDate| {
+            MARGIN + (date - self.start).num_days() as f64 * (WIDTH - 2.0 * MARGIN) / days
+        };
+        let y = |value: f64| HEIGHT - MARGIN - value * (HEIGHT - 2.0 * MARGIN) / max_value;
+        let polyline = |value: DayPointValue| {
+            points
+                .iter()
+                .map(|v| format!("{:.1},{:.1}", x(v.date), y(value(v))))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let mut output = String::new();
+        writeln!(
+            &mut output,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" font-family=\"sans-serif\" font-size=\"11\">"
+        )?;
+        writeln!(
+            &mut output,
+            "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>"
+        )?;
+
+        // Horizontal grid and value labels
+        for step in 0..=5 {
+            let value = max_value * step as f64 / 5.0;
+            writeln!(
+                &mut output,
+                "<line x1=\"{MARGIN}\" y1=\"{0:.1}\" x2=\"{1}\" y2=\"{0:.1}\" stroke=\"#E0E0E0\"/><text x=\"{2}\" y=\"{0:.1}\" text-anchor=\"end\">{value:.0}</text>",
+                y(value),
+                WIDTH - MARGIN,
+                MARGIN - 5.0,
+            )?;
+        }
+
+        // Date labels, about ten of them
+        let label_step = (days as i64 / 10).max(1);
+        let mut date = self.start;
+        while date <= self.end {
+            writeln!(
+                &mut output,
+                "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                x(date),
+                HEIGHT - MARGIN + 15.0,
+                date.format("%m-%d")
+            )?;
+            date += chrono::Duration::days(label_step);
+        }
+
+        writeln!(
+            &mut output,
+            "<line x1=\"{MARGIN}\" y1=\"{0}\" x2=\"{1}\" y2=\"{0}\" stroke=\"black\"/><line x1=\"{MARGIN}\" y1=\"{MARGIN}\" x2=\"{MARGIN}\" y2=\"{0}\" stroke=\"black\"/>",
+            HEIGHT - MARGIN,
+            WIDTH - MARGIN,
+        )?;
+
+        if let Some(first) = points.first() {
+            writeln!(
+                &mut output,
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#9E9E9E\" stroke-dasharray=\"5,5\"/>",
+                x(self.start),
+                y(first.remaining()),
+                x(self.end),
+                y(0.0),
+            )?;
+        }
+
+        let lines: [(&str, DayPointValue); 3] = [
+            ("#2E56A6", |v| v.scope),
+            ("#A65229", |v| v.remaining()),
+            ("#2E8B57", |v| v.completed),
+        ];
+        for (color, value) in lines {
+            writeln!(
+                &mut output,
+                "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"/>",
+                color,
+                polyline(value)
+            )?;
+        }
+
+        for point in points.iter().filter(|v| v.added > 0.0) {
+            writeln!(
+                &mut output,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"5\" fill=\"#FF9800\"><title>+{} ({})</title></circle>",
+                x(point.date),
+                y(point.scope),
+                point.added,
+                point.date
+            )?;
+        }
+
+        for (n, (color, title)) in [
+            ("#2E56A6", "Объем"),
+            ("#A65229", "Осталось"),
+            ("#2E8B57", "Сделано"),
+            ("#9E9E9E", "Идеально"),
+            ("#FF9800", "Добавлено в ходе периода"),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let left = MARGIN + n as f64 * 150.0;
+            writeln!(
+                &mut output,
+                "<rect x=\"{left}\" y=\"15\" width=\"12\" height=\"12\" fill=\"{color}\"/><text x=\"{}\" y=\"25\">{title}</text>",
+                left + 16.0
+            )?;
+        }
+
+        writeln!(&mut output, "</svg>")?;
+        Ok(output)
+    }
+
+    pub fn generate(
+        &self,
+        points: &[DayPoint],
+        histories: &[IssueHistory],
+        dates: &DateDisplay,
+    ) -> Result<String> {
+        let mut output = String::new();
+
+        if let Some(description) = &self.description {
+            writeln!(&mut output, "{}", description)?
+        }
+
+        writeln!(&mut output, "\nh1. Burndown ({})\n", self.unit())?;
+        writeln!(&mut output, "!burndown.svg!")?;
+
+        writeln!(&mut output, "\nh1. По дням\n")?;
+        writeln!(
+            &mut output,
+            "|| Дата || Объем || Сделано || Осталось || Добавлено ||"
+        )?;
+        for point in points {
+            writeln!(
+                &mut output,
+                "| {} | {} | {} | {} | {} |",
+                point.date,
+                point.scope,
+                point.completed,
+                point.remaining(),
+                point.added
+            )?
+        }
+
+        let period_start = Self::end_of_day(dates, self.start);
+        let period_end = Self::end_of_day(dates, self.end);
+        let in_period = |v: &chrono::DateTime<chrono::Utc>| *v > period_start && *v <= period_end;
+        let mut added = Vec::new();
+        for history in histories {
+            for moment in history.additions().filter(in_period) {
+                let change = if moment == history.created {
+                    "создана"
+                } else {
+                    "добавлена"
+                };
+                added.push((moment, history, change.to_owned()))
+            }
+            let mut previous = history.initial_estimation;
+            for (moment, estimation) in &history.estimations {
+                if in_period(moment) && history.is_in_scope(*moment) && *estimation > previous {
+                    let change = format!("оценка {} → {}", previous, estimation);
+                    added.push((*moment, history, change))
+                }
+                previous = *estimation
+            }
+        }
+        added.sort_by(|a, b| (a.0, &a.1.key).cmp(&(b.0, &b.1.key)));
+        if !added.is_empty() {
+            writeln!(&mut output, "\nh1. Добавлено в ходе периода\n")?;
+            writeln!(&mut output, "|| Дата || Задача || Описание || Изменение ||")?;
+            for (moment, history, change) in added {
+                writeln!(
+                    &mut output,
+                    "| {} | {} | {} | {} |",
+                    dates.date(moment),
+                    history.key,
+                    crate::confluence::wiki_escape(&history.summary),
+                    change
+                )?
+            }
+        }
+
+        Ok(output)
+    }
+
+    pub async fn make(&self, config: Arc<crate::config::Config>) -> Result<()> {
+        use std::io::Write;
+
+        if self.end < self.start {
+            bail!("Burndown period ends before it starts")
+        }
+
+        let issues_list = self
+            .query_set
+            .get_issues_expanded(config.clone(), &["changelog"])
+            .await?;
+        let histories: Vec<_> = issues_list
+            .iter()
+            .filter_map(|issue| {
+                IssueHistory::of_issue(
+                    issue,
+                    &self.story_points_field,
+                    &self.scope_field,
+                    self.scope_value.as_deref(),
+                )
+            })
+            .collect();
+        // Days are split in the timezone shared by all Jira instances
+        let points = self.calculate(&histories, &config.dates);
+
+        let wiki_content = self.generate(&points, &histories, &config.dates)?;
+
+        let mut svg = tempfile::NamedTempFile::new()?;
+        svg.write_all(self.generate_svg(&points)?.as_bytes())?;
+
+        let current_content = self.confluence.get_page(&self.space, &self.title).await?;
+
+        let id: u64 = current_content.id.parse()?;
+
+        self.confluence
+            .upload_attachment(id, svg.path(), "burndown.svg")
+            .await?;
+
+        let _result = self
+            .confluence
+            .update_content(
+                id,
+                crate::confluence_content_update::UpdateContentBody {
+                    version: crate::confluence_content_update::UpdateContentBodyVersion {
+                        number: current_content.version.number + 1,
+                    },
+                    title: current_content.title.clone(),
+                    content_type: crate::confluence_types::ContentType::Page,
+                    body: crate::confluence_types::ContentBody {
+                        storage: crate::confluence_types::ContentBodyStorage {
+                            value: wiki_content,
+                            representation: crate::confluence_types::ContentRepresentation::Wiki,
+                        },
+                    },
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Burndown, IssueHistory};
+    use crate::jira::{parse_jira_datetime, DateDisplay};
+    use crate::report::ReportIssue;
+
+    fn moment(s: &str) -> chrono::DateTime<chrono::Utc> {
+        parse_jira_datetime(s).unwrap()
+    }
+
+    /// Issue created before the period with sprint changes of (moment, from, to)
+    fn issue(key: &str, resolved: Option<&str>, sprints: &[(&str, &str, &str)]) -> ReportIssue {
+        let mut issue = crate::report_data::test_data::issue(key, None, None, resolved);
+        issue.issue.fields.created = "2022-09-30T10:00:00.000+0000".to_owned();
+        let histories: Vec<_> = sprints
+            .iter()
+            .map(|(created, from, to)| {
+                let value = |v: &str| if v.is_empty() { json!(null) } else { json!(v) };
+                json!({
+                    "created": created,
+                    "items": [{
+                        "field": "Sprint",
+                        "fieldtype": "custom",
+                        "from": null,
+                        "fromString": value(from),
+                        "to": null,
+                        "toString": value(to),
+                    }],
+                })
+            })
+            .collect();
+        issue.issue.changelog =
+            Some(serde_json::from_value(json!({ "histories": histories })).unwrap());
+        issue
+    }
+
+    fn history(issue: &ReportIssue, scope_value: Option<&str>) -> IssueHistory {
+        IssueHistory::of_issue(issue, &None, "Sprint", scope_value).unwrap()
+    }
+
+    fn burndown() -> Burndown {
+        serde_yaml::from_str(
+            "query_set: []\n\
+             confluence:\n  base_url: https://wiki.test/\n  access:\n    Token:\n      String: x\n\
+             space: DOCS\ntitle: Burndown\nstart: 2022-10-03\nend: 2022-10-07\n",
+        )
+        .unwrap()
+    }
+
+    /// Issues of the sprint "Sprint 1" running 2022-10-03 - 2022-10-07
+    fn issues() -> Vec<ReportIssue> {
+        vec![
+            // Created in the sprint and resolved during it
+            issue("T-1", Some("2022-10-06T10:00:00.000+0000"), &[]),
+            // Added in the middle of the period
+            issue(
+                "T-2",
+                None,
+                &[("2022-10-04T20:00:00.000+0000", "", "Sprint 1")],
+            ),
+            // Carried over to the next sprint after the period
+            issue(
+                "T-3",
+                None,
+                &[(
+                    "2022-10-10T10:00:00.000+0000",
+                    "Sprint 1",
+                    "Sprint 1, Sprint 2",
+                )],
+            ),
+            // Removed from the sprint
+            issue(
+                "T-4",
+                None,
+                &[("2022-10-04T12:00:00.000+0000", "Sprint 1", "")],
+            ),
+        ]
+    }
+
+    #[test]
+    fn issue_created_in_scope() {
+        let history = history(&issues()[0], None);
+        assert!(!history.is_in_scope(moment("2022-09-30T09:00:00.000+0000")));
+        assert!(history.is_in_scope(moment("2022-10-03T10:00:00.000+0000")));
+        assert_eq!(
+            history.additions().collect::<Vec<_>>(),
+            vec![moment("2022-09-30T10:00:00.000+0000")]
+        );
+    }
+
+    #[test]
+    fn issue_added_during_period() {
+        let history = history(&issues()[1], Some("Sprint 1"));
+        assert!(!history.is_in_scope(moment("2022-10-04T10:00:00.000+0000")));
+        assert!(history.is_in_scope(moment("2022-10-05T10:00:00.000+0000")));
+        assert_eq!(
+            history.additions().collect::<Vec<_>>(),
+            vec![moment("2022-10-04T20:00:00.000+0000")]
+        );
+    }
+
+    #[test]
+    fn carried_over_issue_stays_in_scope() {
+        for scope_value in [None, Some("Sprint 1")] {
+            let history = history(&issues()[2], scope_value);
+            assert!(history.is_in_scope(moment("2022-10-03T10:00:00.000+0000")));
+            assert!(history.is_in_scope(moment("2022-10-07T10:00:00.000+0000")));
+            assert!(history.is_in_scope(moment("2022-10-11T10:00:00.000+0000")));
+        }
+        let history = history(
+            &issue(
+                "T-3",
+                None,
+                &[("2022-10-10T10:00:00.000+0000", "Sprint 1", "Sprint 2")],
+            ),
+            Some("Sprint 1"),
+        );
+        assert!(history.is_in_scope(moment("2022-10-07T10:00:00.000+0000")));
+        assert!(!history.is_in_scope(moment("2022-10-11T10:00:00.000+0000")));
+    }
+
+    #[test]
+    fn removed_issue_leaves_scope() {
+        let history = history(&issues()[3], None);
+        assert!(history.is_in_scope(moment("2022-10-04T10:00:00.000+0000")));
+        assert!(!history.is_in_scope(moment("2022-10-04T13:00:00.000+0000")));
+    }
+
+    #[test]
+    fn repeated_additions_keep_the_first() {
+        let history = history(
+            &issue(
+                "T-5",
+                None,
+                &[
+                    ("2022-10-04T10:00:00.000+0000", "", "Sprint 1"),
+                    (
+                        "2022-10-05T10:00:00.000+0000",
+                        "Sprint 1",
+                        "Sprint 1, Sprint 2",
+                    ),
+                    (
+                        "2022-10-06T10:00:00.000+0000",
+                        "Sprint 1, Sprint 2",
+                        "Sprint 2",
+                    ),
+                ],
+            ),
+            None,
+        );
+        assert_eq!(
+            history.additions().collect::<Vec<_>>(),
+            vec![moment("2022-10-04T10:00:00.000+0000")]
+        );
+        assert!(history.is_in_scope(moment("2022-10-07T10:00:00.000+0000")));
+    }
+
+    fn points(dates: &DateDisplay) -> Vec<(String, f64, f64, f64)> {
+        let histories: Vec<_> = issues()
+            .iter()
+            .map(|v| history(v, Some("Sprint 1")))
+            .collect();
+        burndown()
+            .calculate(&histories, dates)
+            .iter()
+            .map(|v| (v.date.to_string(), v.scope, v.completed, v.added))
+            .collect()
+    }
+
+    #[test]
+    fn calculate_by_days() {
+        assert_eq!(
+            points(&DateDisplay::default()),
+            vec![
+                ("2022-10-03".to_owned(), 3.0, 0.0, 0.0),
+                ("2022-10-04".to_owned(), 3.0, 0.0, 1.0),
+                ("2022-10-05".to_owned(), 3.0, 0.0, 0.0),
+                ("2022-10-06".to_owned(), 3.0, 1.0, 0.0),
+                ("2022-10-07".to_owned(), 3.0, 1.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn calculate_days_in_timezone() {
+        let dates = DateDisplay {
+            timezone: chrono_tz::Asia::Tokyo,
+            ..DateDisplay::default()
+        };
+        // T-2 is added at 05:00 of 2022-10-05 in Tokyo
+        assert_eq!(
+            points(&dates),
+            vec![
+                ("2022-10-03".to_owned(), 3.0, 0.0, 0.0),
+                ("2022-10-04".to_owned(), 2.0, 0.0, 0.0),
+                ("2022-10-05".to_owned(), 3.0, 0.0, 1.0),
+                ("2022-10-06".to_owned(), 3.0, 1.0, 0.0),
+                ("2022-10-07".to_owned(), 3.0, 1.0, 0.0),
+            ]
+        );
+    }
+}
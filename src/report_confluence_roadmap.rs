@@ -1,7 +1,7 @@
 use std::fmt::Write;
 use std::{collections::HashSet, sync::Arc};
 
-use anyhow::{bail, Result};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use crate::report_data::IssueID;
@@ -30,11 +30,53 @@ pub struct ConfluenceRoadmap {
     show_team_roadmaps: bool,
     #[serde(default = "default_dependencies_deepness")]
     dependencies_deepness: usize,
+    /// Levels of sub-tasks, parents and epic hierarchy to fetch. Progress of hierarchy parents is
+    /// shown if set
+    #[serde(default)]
+    hierarchy_deepness: usize,
     #[serde(default)]
     foreign_relations: Vec<crate::report::ForeignRelation>,
+    #[serde(default)]
+    dependency_graph: crate::report_dependency_graph::DependencyGraph,
+    /// Show critical path through dependencies and highlight it in the graph
+    #[serde(default)]
+    critical_path: Option<crate::report_critical_path::CriticalPath>,
 }
 
 impl ConfluenceRoadmap {
+    /// Confluence page updated by the report
+    pub fn page(&self) -> crate::report::ReportPage<'_> {
+        crate::report::ReportPage {
+            confluence: &self.confluence,
+            space: &self.space,
+            title: &self.title,
+        }
+    }
+
+    /// Jira queries of the report
+    pub fn queries(&self) -> Vec<&crate::config::JiraQuery> {
+        self.query_set.queries().iter().collect()
+    }
+
+    pub fn foreign_relations(&self) -> &[crate::report::ForeignRelation] {
+        &self.foreign_relations
+    }
+
+    fn graph_highlights(
+        &self,
+        data: &crate::report_data::ReportData,
+    ) -> crate::report_dependency_graph::GraphHighlights {
+        let highlights = match &self.critical_path {
+            None => crate::report_dependency_graph::GraphHighlights::default(),
+            Some(v) => crate::report_dependency_graph::GraphHighlights::of_critical_path(
+                &v.calculate(data),
+            ),
+        };
+        highlights
+            .with_cycles(&crate::report_cycles::Cycles::find(data))
+            .with_schedule(&crate::report_schedule::ScheduleCheck::find(data))
+    }
+
     fn get_task(&self, issue: &crate::report::ReportIssue) -> String {
         let mut col1 = format!(
             " *{}*",
@@ -162,14 +204,11 @@ impl ConfluenceRoadmap {
             }
         }) {
             if let Some(epic_name) = &epic.custom_fields.epic_name {
-                let mut issue_url = epic.jira.base_url.clone();
-                issue_url.set_path(&format!("browse/{}", epic.issue.key));
-
                 writeln!(
                     &mut result,
                     "| [{}|{}] | {} | {} |",
                     crate::confluence::wiki_escape(epic_name),
-                    issue_url,
+                    epic.url(),
                     crate::confluence::wiki_escape(epic.issue.fields.summary.as_str()),
                     crate::confluence::wiki_escape(
                         epic.custom_fields.reason.as_deref().unwrap_or_default()
@@ -239,8 +278,39 @@ impl ConfluenceRoadmap {
             )?;
         }
 
+        if !data.hierarchy.is_empty() {
+            writeln!(&mut output, "\nh1. Прогресс по иерархии\n")?;
+            writeln!(
+                &mut output,
+                "{}",
+                crate::report_hierarchy::Hierarchy::new(data).confluence_wiki()?
+            )?;
+        }
+
+        let cycles = crate::report_cycles::Cycles::find(data);
+        if !cycles.cycles.is_empty() {
+            writeln!(&mut output, "\nh1. Циклические зависимости\n")?;
+            writeln!(&mut output, "{}", cycles.confluence_wiki(data)?)?;
+        }
+
+        let schedule = crate::report_schedule::ScheduleCheck::find(data);
+        if !schedule.problems.is_empty() {
+            writeln!(&mut output, "\nh1. Несогласованные сроки\n")?;
+            writeln!(&mut output, "{}", schedule.confluence_wiki(data)?)?;
+        }
+
+        if let Some(critical_path) = &self.critical_path {
+            writeln!(&mut output, "\nh1. Критический путь\n")?;
+            writeln!(&mut output, "{}", critical_path.confluence_wiki(data)?)?;
+        }
+
         writeln!(&mut output, "\nh1. Граф зависимостей\n")?;
-        writeln!(&mut output, "!dependency_graph.svg!")?;
+        writeln!(
+            &mut output,
+            "{}",
+            self.dependency_graph
+                .confluence_wiki(data, &self.graph_highlights(data))?
+        )?;
 
         Ok(output)
     }
@@ -250,35 +320,39 @@ impl ConfluenceRoadmap {
         page_id: u64,
         data: &crate::report_data::ReportData,
     ) -> Result<()> {
-        let generator = crate::report_dependency_graph::DependencyGraph;
-        let svg = generator.make(data)?;
+        let graph = self
+            .dependency_graph
+            .make(data, &self.graph_highlights(data))?;
         self.confluence
-            .upload_attachment(page_id, svg.path(), "dependency_graph.svg")
+            .upload_attachment(
+                page_id,
+                graph.path(),
+                &self.dependency_graph.attachment_name(),
+            )
             .await?;
         Ok(())
     }
 
-    pub async fn make(&self, config: Arc<crate::config::Config>) -> Result<()> {
+    /// Fails before the page is updated if `strict` is set and dependencies have cycles
+    pub async fn make(&self, config: Arc<crate::config::Config>, strict: bool) -> Result<()> {
         let issues_list = self.query_set.get_issues(config).await?;
         let data = crate::report_data::ReportData::of_slice(
             &self.foreign_relations,
             &issues_list,
             self.dependencies_deepness,
+            self.hierarchy_deepness,
         )
         .await?;
 
-        let wiki_content = self.generate(&data)?;
+        let cycles = crate::report_cycles::Cycles::find(&data);
+        cycles.warn();
+        if strict {
+            cycles.check()?;
+        }
 
-        let get_result = self
-            .confluence
-            .get_content(&self.space, &self.title)
-            .await
-            .unwrap();
+        let wiki_content = self.generate(&data)?;
 
-        let current_content = match get_result.results.first() {
-            None => bail!("Page not found"),
-            Some(v) => v,
-        };
+        let current_content = self.confluence.get_page(&self.space, &self.title).await?;
 
         let id: u64 = current_content.id.parse()?;
 
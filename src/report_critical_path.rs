@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::report_data::IssueID;
+
+fn default_duration() -> i64 {
+    5
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CriticalPath {
+    /// Duration in days of issues without planned dates
+    #[serde(default = "default_duration")]
+    default_duration: i64,
+}
+
+impl Default for CriticalPath {
+    fn default() -> Self {
+        Self {
+            default_duration: default_duration(),
+        }
+    }
+}
+
+/// Schedule of a single issue, in days from the beginning of the plan
+pub struct IssueTiming {
+    pub duration: i64,
+    pub earliest_start: i64,
+    pub earliest_finish: i64,
+    pub latest_start: i64,
+}
+
+impl IssueTiming {
+    /// How long the issue can be delayed without moving the delivery date
+    pub fn slack(&self) -> i64 {
+        self.latest_start - self.earliest_start
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.slack() == 0
+    }
+}
+
+pub struct CriticalPathResult {
+    pub timings: HashMap<IssueID, IssueTiming>,
+    /// Relations between critical issues which form the critical path
+    pub relations: HashSet<(IssueID, IssueID)>,
+    /// Duration of the whole plan in days
+    pub duration: i64,
+}
+
+impl CriticalPath {
+    fn duration(&self, issue: &crate::report::ReportIssue) -> i64 {
+        match (
+            issue.custom_fields.planned_start,
+            issue.custom_fields.planned_end,
+        ) {
+            (Some(start), Some(end)) if end >= start => (end - start).num_days().max(1),
+            _ => self.default_duration,
+        }
+    }
+
+    /// Critical path method over dependency relations. Issues in cycles are skipped
+    pub fn calculate(&self, data: &crate::report_data::ReportData) -> CriticalPathResult {
+        let durations: HashMap<_, _> = data
+            .issues
+            .all()
+            .iter()
+            .filter(|(_, issue)| issue.entity_type != crate::report::ReportIssueType::Epic)
+            .map(|(id, issue)| (id.clone(), self.duration(issue)))
+            .collect();
+
+        let edges: HashSet<_> = data
+            .relations
+            .iter()
+            .filter(|v| v.kind.ordering)
+            .filter(|v| {
+                v.from != v.to && durations.contains_key(&v.from) && durations.contains_key(&v.to)
+            })
+            .map(|v| (v.from.clone(), v.to.clone()))
+            .collect();
+
+        let mut successors: HashMap<&IssueID, Vec<&IssueID>> = HashMap::new();
+        let mut predecessors: HashMap<&IssueID, Vec<&IssueID>> = HashMap::new();
+        for (from, to) in &edges {
+            successors.entry(from).or_default().push(to);
+            predecessors.entry(to).or_default().push(from);
+        }
+
+        // Kahn's topological sort, issues left unsorted are in cycles
+        let mut in_degree: HashMap<&IssueID, usize> = durations
+            .keys()
+            .map(|id| (id, predecessors.get(id).map(|v| v.len()).unwrap_or(0)))
+            .collect();
+        let mut queue: Vec<_> = in_degree
+            .iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(id, _)| *id)
+            .collect();
+        let mut order = Vec::new();
+        while let Some(id) = queue.pop() {
+            order.push(id);
+            for next in successors.get(id).map(|v| v.as_slice()).unwrap_or_default() {
+                if let Some(degree) = in_degree.get_mut(next) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        queue.push(next)
+                    }
+                }
+            }
+        }
+        if order.len() < durations.len() {
+            slog_scope::warn!(
+                "{} issues are in dependency cycles and skipped by critical path analysis",
+                durations.len() - order.len()
+            );
+        }
+
+        let mut earliest_finish: HashMap<&IssueID, i64> = HashMap::new();
+        for id in &order {
+            let start = predecessors
+                .get(id)
+                .map(|v| v.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|v| earliest_finish.get(v))
+                .max()
+                .copied()
+                .unwrap_or(0);
+            let _ = earliest_finish.insert(id, start + durations[*id]);
+        }
+        let duration = earliest_finish.values().max().copied().unwrap_or(0);
+
+        let mut latest_start: HashMap<&IssueID, i64> = HashMap::new();
+        for id in order.iter().rev() {
+            let finish = successors
+                .get(id)
+                .map(|v| v.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|v| latest_start.get(v))
+                .min()
+                .copied()
+                .unwrap_or(duration);
+            let _ = latest_start.insert(id, finish - durations[*id]);
+        }
+
+        let timings: HashMap<_, _> = order
+            .iter()
+            .map(|id| {
+                let duration = durations[*id];
+                (
+                    (*id).clone(),
+                    IssueTiming {
+                        duration,
+                        earliest_start: earliest_finish[id] - duration,
+                        earliest_finish: earliest_finish[id],
+                        latest_start: latest_start[id],
+                    },
+                )
+            })
+            .collect();
+
+        let relations = edges
+            .into_iter()
+            .filter(|(from, to)| match (timings.get(from), timings.get(to)) {
+                (Some(from), Some(to)) => {
+                    from.is_critical()
+                        && to.is_critical()
+                        && from.earliest_finish == to.earliest_start
+                }
+                _ => false,
+            })
+            .collect();
+
+        CriticalPathResult {
+            timings,
+            relations,
+            duration,
+        }
+    }
+
+    pub fn confluence_wiki(&self, data: &crate::report_data::ReportData) -> Result<String> {
+        let result = self.calculate(data);
+
+        let mut output = String::new();
+        writeln!(
+            &mut output,
+            "Длительность плана: {} дн. Задержка задачи больше ее резерва сдвигает срок поставки.\n",
+            result.duration
+        )?;
+        writeln!(
+            &mut output,
+            "|| Описание таска || Jira-таск || Длительность || Старт || Финиш || Резерв ||"
+        )?;
+
+        let mut timings: Vec<_> = result.timings.iter().collect();
+        timings.sort_by_key(|(id, v)| (v.slack(), v.earliest_start, id.issue.clone()));
+        for (id, timing) in timings {
+            let issue = match data.issues.all().get(id) {
+                None => continue,
+                Some(v) => v,
+            };
+            let summary = crate::confluence::wiki_escape(&issue.issue.fields.summary);
+            let summary = if timing.is_critical() {
+                format!("*{}*", summary)
+            } else {
+                summary
+            };
+            writeln!(
+                &mut output,
+                "| {} | {} | {} | {} | {} | {} |",
+                summary,
+                issue.confluence_wiki_url(false),
+                timing.duration,
+                timing.earliest_start,
+                timing.earliest_finish,
+                timing.slack()
+            )?
+        }
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CriticalPath;
+    use crate::report_data::test_data::{data, id, issue};
+
+    #[test]
+    fn diamond() {
+        // A (1 day) is followed by B (3 days) and C (1 day), both followed by D (1 day)
+        let issues = [
+            issue("T-1", Some("2022-10-01"), Some("2022-10-02"), None),
+            issue("T-2", Some("2022-10-02"), Some("2022-10-05"), None),
+            issue("T-3", Some("2022-10-02"), Some("2022-10-03"), None),
+            issue("T-4", Some("2022-10-05"), Some("2022-10-06"), None),
+        ];
+        let data = data(
+            &issues,
+            &[
+                ("T-1", "T-2"),
+                ("T-1", "T-3"),
+                ("T-2", "T-4"),
+                ("T-3", "T-4"),
+            ],
+        );
+        let result = CriticalPath::default().calculate(&data);
+
+        assert_eq!(result.duration, 5);
+        for key in ["T-1", "T-2", "T-4"] {
+            assert!(
+                result.timings[&id(key)].is_critical(),
+                "{} is critical",
+                key
+            );
+        }
+        let short = &result.timings[&id("T-3")];
+        assert_eq!((short.earliest_start, short.latest_start), (1, 3));
+        assert_eq!(short.slack(), 2);
+        assert_eq!(
+            result.relations,
+            [(id("T-1"), id("T-2")), (id("T-2"), id("T-4"))]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn unscheduled_issues_take_default_duration() {
+        let issues = [
+            issue("T-1", None, None, None),
+            issue("T-2", None, None, None),
+        ];
+        let result = CriticalPath::default().calculate(&data(&issues, &[("T-1", "T-2")]));
+        assert_eq!(result.duration, 10);
+        assert_eq!(result.timings[&id("T-2")].earliest_start, 5);
+    }
+
+    #[test]
+    fn issues_in_cycles_are_skipped() {
+        let issues = [
+            issue("T-1", None, None, None),
+            issue("T-2", None, None, None),
+            issue("T-3", None, None, None),
+        ];
+        let result = CriticalPath::default().calculate(&data(
+            &issues,
+            &[("T-1", "T-2"), ("T-2", "T-1"), ("T-1", "T-3")],
+        ));
+        assert_eq!(result.timings.len(), 0);
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+
+use crate::report_data::IssueID;
+
+/// Closed chain of dependencies, the last issue depends on the first one
+pub struct Cycle(pub Vec<IssueID>);
+
+impl Cycle {
+    pub fn as_string(&self) -> String {
+        self.0
+            .iter()
+            .chain(self.0.first())
+            .map(|v| v.issue.as_str())
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+}
+
+pub struct Cycles {
+    pub cycles: Vec<Cycle>,
+    /// Relations inside strongly connected components as (from, to) pairs
+    pub relations: HashSet<(IssueID, IssueID)>,
+}
+
+struct Tarjan<'a> {
+    successors: &'a HashMap<&'a IssueID, Vec<&'a IssueID>>,
+    index: usize,
+    indices: HashMap<&'a IssueID, usize>,
+    low_links: HashMap<&'a IssueID, usize>,
+    stack: Vec<&'a IssueID>,
+    on_stack: HashSet<&'a IssueID>,
+    components: Vec<Vec<&'a IssueID>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, id: &'a IssueID) {
+        let _ = self.indices.insert(id, self.index);
+        let _ = self.low_links.insert(id, self.index);
+        self.index += 1;
+        self.stack.push(id);
+        let _ = self.on_stack.insert(id);
+
+        for next in self
+            .successors
+            .get(id)
+            .map(|v| v.as_slice())
+            .unwrap_or_default()
+        {
+            if !self.indices.contains_key(next) {
+                self.visit(next);
+                let low_link = self.low_links[id].min(self.low_links[next]);
+                let _ = self.low_links.insert(id, low_link);
+            } else if self.on_stack.contains(next) {
+                let low_link = self.low_links[id].min(self.indices[next]);
+                let _ = self.low_links.insert(id, low_link);
+            }
+        }
+
+        if self.low_links[id] == self.indices[id] {
+            let mut component = Vec::new();
+            while let Some(v) = self.stack.pop() {
+                let _ = self.on_stack.remove(v);
+                component.push(v);
+                if v == id {
+                    break;
+                }
+            }
+            self.components.push(component)
+        }
+    }
+}
+
+impl Cycles {
+    /// Shortest chain from `start` back to itself inside the component
+    fn shortest_cycle(
+        successors: &HashMap<&IssueID, Vec<&IssueID>>,
+        component: &HashSet<&IssueID>,
+        start: &IssueID,
+    ) -> Vec<IssueID> {
+        let mut previous: HashMap<&IssueID, &IssueID> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(id) = queue.pop_front() {
+            for next in successors.get(id).map(|v| v.as_slice()).unwrap_or_default() {
+                if !component.contains(next) || previous.contains_key(next) {
+                    continue;
+                }
+                let _ = previous.insert(next, id);
+                if *next == start {
+                    queue.clear();
+                    break;
+                }
+                queue.push_back(next)
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut current = start;
+        while let Some(v) = previous.get(current) {
+            if *v == start {
+                break;
+            }
+            path.push((*v).clone());
+            current = v;
+        }
+        path.push(start.clone());
+        path.reverse();
+        path
+    }
+
+    /// Cycles in ordering relations, including relations between Jira instances
+    pub fn find(data: &crate::report_data::ReportData) -> Self {
+        let edges: Vec<_> = data
+            .relations
+            .iter()
+            .filter(|v| v.kind.ordering)
+            .map(|v| (&v.from, &v.to))
+            .collect();
+
+        let mut successors: HashMap<&IssueID, Vec<&IssueID>> = HashMap::new();
+        for (from, to) in &edges {
+            successors.entry(from).or_default().push(to);
+        }
+        let mut nodes: Vec<_> = successors.keys().copied().collect();
+        nodes.sort_by_key(|v| v.as_string());
+
+        let mut tarjan = Tarjan {
+            successors: &successors,
+            index: 0,
+            indices: HashMap::new(),
+            low_links: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new(),
+        };
+        for id in nodes {
+            if !tarjan.indices.contains_key(id) {
+                tarjan.visit(id)
+            }
+        }
+
+        let mut cycles = Vec::new();
+        let mut relations = HashSet::new();
+        for component in tarjan.components {
+            let component: HashSet<_> = component.into_iter().collect();
+            let cyclic = component.len() > 1
+                || edges
+                    .iter()
+                    .any(|(from, to)| from == to && component.contains(from));
+            if !cyclic {
+                continue;
+            }
+            for (from, to) in &edges {
+                if component.contains(from) && component.contains(to) {
+                    let _ = relations.insert(((*from).clone(), (*to).clone()));
+                }
+            }
+            let start = component
+                .iter()
+                .min_by_key(|v| v.as_string())
+                .copied()
+                .unwrap_or_else(|| unreachable!("strongly connected component is never empty"));
+            cycles.push(Cycle(Self::shortest_cycle(&successors, &component, start)))
+        }
+        cycles.sort_by_key(|v| v.as_string());
+
+        Self { cycles, relations }
+    }
+
+    pub fn warn(&self) {
+        for cycle in &self.cycles {
+            slog_scope::warn!("Dependency cycle found: {}", cycle.as_string());
+        }
+    }
+
+    /// Fails if any cycle is found
+    pub fn check(&self) -> Result<()> {
+        if !self.cycles.is_empty() {
+            bail!(
+                "Dependency cycles found: {}",
+                self.cycles
+                    .iter()
+                    .map(|v| v.as_string())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            )
+        }
+        Ok(())
+    }
+
+    pub fn confluence_wiki(&self, data: &crate::report_data::ReportData) -> Result<String> {
+        let mut output = String::new();
+        writeln!(&mut output, "|| # || Цикл ||")?;
+        for (n, cycle) in self.cycles.iter().enumerate() {
+            let chain = cycle
+                .0
+                .iter()
+                .chain(cycle.0.first())
+                .map(|id| match data.issues.all().get(id) {
+                    None => id.issue.clone(),
+                    Some(issue) => issue.confluence_wiki_url(false),
+                })
+                .collect::<Vec<_>>()
+                .join(" → ");
+            writeln!(&mut output, "| {} | {} |", n + 1, chain)?
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cycles;
+    use crate::report_data::test_data::{data, id, issue};
+
+    fn issues(keys: &[&str]) -> Vec<crate::report::ReportIssue> {
+        keys.iter().map(|v| issue(v, None, None, None)).collect()
+    }
+
+    #[test]
+    fn self_loop() {
+        let cycles = Cycles::find(&data(&issues(&["T-1"]), &[("T-1", "T-1")]));
+        assert_eq!(cycles.cycles.len(), 1);
+        assert_eq!(cycles.cycles[0].as_string(), "T-1 → T-1");
+        assert!(cycles.relations.contains(&(id("T-1"), id("T-1"))));
+        assert!(cycles.check().is_err());
+    }
+
+    #[test]
+    fn two_nodes() {
+        let cycles = Cycles::find(&data(
+            &issues(&["T-1", "T-2", "T-3"]),
+            &[("T-1", "T-2"), ("T-2", "T-1"), ("T-2", "T-3")],
+        ));
+        assert_eq!(cycles.cycles.len(), 1);
+        assert_eq!(cycles.cycles[0].as_string(), "T-1 → T-2 → T-1");
+        assert_eq!(
+            cycles.relations,
+            [(id("T-1"), id("T-2")), (id("T-2"), id("T-1"))]
+                .into_iter()
+                .collect()
+        );
+    }
+
+    #[test]
+    fn shortest_cycle_of_component() {
+        // T-1 → T-2 → T-3 → T-1 with a shortcut T-2 → T-1
+        let cycles = Cycles::find(&data(
+            &issues(&["T-1", "T-2", "T-3"]),
+            &[
+                ("T-1", "T-2"),
+                ("T-2", "T-3"),
+                ("T-3", "T-1"),
+                ("T-2", "T-1"),
+            ],
+        ));
+        assert_eq!(cycles.cycles.len(), 1);
+        assert_eq!(cycles.cycles[0].as_string(), "T-1 → T-2 → T-1");
+        assert_eq!(cycles.relations.len(), 4);
+    }
+
+    #[test]
+    fn no_cycles_in_dag() {
+        let cycles = Cycles::find(&data(
+            &issues(&["T-1", "T-2", "T-3"]),
+            &[("T-1", "T-2"), ("T-1", "T-3"), ("T-2", "T-3")],
+        ));
+        assert!(cycles.cycles.is_empty());
+        assert!(cycles.relations.is_empty());
+        assert!(cycles.check().is_ok());
+    }
+}
@@ -101,10 +101,26 @@ pub struct Relation {
     pub kind: crate::report::RelationKind,
 }
 
+#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug, serde::Serialize)]
+pub enum HierarchyKind {
+    Subtask,
+    /// Advanced Roadmaps "Parent Link"
+    ParentLink,
+    Epic,
+}
+
+#[derive(Hash, PartialEq, Eq)]
+pub struct HierarchyRelation {
+    pub parent: IssueID,
+    pub child: IssueID,
+    pub kind: HierarchyKind,
+}
+
 pub struct ReportData {
     pub issues: IssuesList,
     pub epics: IssuesList,
     pub relations: HashSet<Relation>,
+    pub hierarchy: HashSet<HierarchyRelation>,
 }
 
 impl ReportData {
@@ -126,17 +142,6 @@ impl ReportData {
                     None => return None,
                     Some(v) => v,
                 };
-                let kind = match issue
-                    .jira
-                    .relations_map
-                    .iter()
-                    .filter(|map| &map.name == kind)
-                    .collect::<Vec<_>>()
-                    .first()
-                {
-                    Some(map) => &map.equals_to,
-                    None => kind,
-                };
                 Some((issue.jira.clone(), key.to_owned(), kind.to_owned(), true))
             }
             Some(inward_issue) => {
@@ -148,17 +153,6 @@ impl ReportData {
                     None => return None,
                     Some(v) => v,
                 };
-                let kind = match issue
-                    .jira
-                    .relations_map
-                    .iter()
-                    .filter(|map| &map.name == kind)
-                    .collect::<Vec<_>>()
-                    .first()
-                {
-                    Some(map) => &map.equals_to,
-                    None => kind,
-                };
                 Some((issue.jira.clone(), key.to_owned(), kind.to_owned(), false))
             }
         }
@@ -235,66 +229,29 @@ impl ReportData {
                             IssueID::new(&link_jira, &link_key),
                         )
                     };
-                    let relation_added = match kind.as_str() {
-                        "dependance for" => {
-                            let _ = relations.insert(Relation {
-                                from: issue1,
-                                to: issue2,
-                                kind: crate::report::RelationKind::Dependance,
-                            });
-                            true
-                        }
-                        "depends on" => {
-                            let _ = relations.insert(Relation {
-                                from: issue2,
-                                to: issue1,
-                                kind: crate::report::RelationKind::Dependance,
-                            });
-                            true
-                        }
-                        "mentioned in" | "relates to" => {
-                            let _ = relations.insert(Relation {
-                                from: issue1,
-                                to: issue2,
-                                kind: crate::report::RelationKind::Mention,
-                            });
-                            true
-                        }
-                        "mentions" => {
-                            let _ = relations.insert(Relation {
-                                from: issue2,
-                                to: issue1,
-                                kind: crate::report::RelationKind::Mention,
-                            });
-                            true
-                        }
-                        "blocks" => {
-                            let _ = relations.insert(Relation {
-                                from: issue1,
-                                to: issue2,
-                                kind: crate::report::RelationKind::Block,
-                            });
-                            true
-                        }
-                        "is blocked by" => {
-                            let _ = relations.insert(Relation {
-                                from: issue2,
-                                to: issue1,
-                                kind: crate::report::RelationKind::Block,
-                            });
-                            true
-                        }
-                        _ => {
+                    let (relation_kind, outward) = match issue.jira.relation_kind(&kind) {
+                        None => {
                             slog_scope::error!(
                                 "Unknown relation kind {:?} in {:?} or {:?}",
                                 kind,
                                 issue.issue.key,
                                 link_key
                             );
-                            false
+                            continue;
                         }
+                        Some(v) => v,
+                    };
+                    let (from, to) = if outward {
+                        (issue1, issue2)
+                    } else {
+                        (issue2, issue1)
                     };
-                    if relation_added && !issue_registered {
+                    let _ = relations.insert(Relation {
+                        from,
+                        to,
+                        kind: relation_kind.clone(),
+                    });
+                    if relation_kind.traverse && !issue_registered {
                         match linked_issue {
                             None => {
                                 let _ = issues_to_fetch.insert(IssueID::new(&link_jira, &link_key));
@@ -403,19 +360,284 @@ impl ReportData {
 
         Ok(epics)
     }
+    /// Parents and children of the issue known from its own fields
+    fn hierarchy_links(issue: &crate::report::ReportIssue) -> Vec<HierarchyRelation> {
+        let id = IssueID::of_issue(issue);
+        let mut r = Vec::new();
+        if let Some(key) = issue
+            .issue
+            .fields
+            .parent
+            .as_ref()
+            .and_then(|v| v.key.as_ref())
+        {
+            r.push(HierarchyRelation {
+                parent: IssueID::new(&issue.jira, key),
+                child: id.clone(),
+                kind: HierarchyKind::Subtask,
+            })
+        }
+        for subtask in issue.issue.fields.subtasks.as_deref().unwrap_or_default() {
+            if let Some(key) = &subtask.key {
+                r.push(HierarchyRelation {
+                    parent: id.clone(),
+                    child: IssueID::new(&issue.jira, key),
+                    kind: HierarchyKind::Subtask,
+                })
+            }
+        }
+        if let Some(key) = &issue.custom_fields.parent_link {
+            r.push(HierarchyRelation {
+                parent: IssueID::new(&issue.jira, key),
+                child: id.clone(),
+                kind: HierarchyKind::ParentLink,
+            })
+        }
+        if let Some(key) = &issue.custom_fields.epic_link {
+            r.push(HierarchyRelation {
+                parent: IssueID::new(&issue.jira, key),
+                child: id,
+                kind: HierarchyKind::Epic,
+            })
+        }
+        r
+    }
+
+    async fn search_issues(
+        jira: &crate::jira::JiraServer,
+        jql: &str,
+        entity_type: crate::report::ReportIssueType,
+    ) -> Result<Vec<ReportIssue>> {
+        let result = jira
+            .search_all(&crate::jira::SearchGetParams::new(jql))
+            .await?;
+        let mut issues = Vec::new();
+        for issue in result {
+            let issue = crate::jira_types::IssueBean::of_json(issue)?;
+            issues.push(ReportIssue::of_issuebean(
+                jira,
+                &issue,
+                entity_type.clone(),
+            )?)
+        }
+        Ok(issues)
+    }
+
+    /// Follows sub-tasks, parents, "Parent Link" and epic links up and down the hierarchy
+    async fn get_hierarchy(
+        issues: &mut IssuesList,
+        epics: &mut IssuesList,
+        deepness: usize,
+    ) -> Result<HashSet<HierarchyRelation>> {
+        let mut hierarchy = HashSet::new();
+        if deepness == 0 {
+            return Ok(hierarchy);
+        }
+
+        slog_scope::info!("Fetching hierarchy for issues list");
+
+        let mut issues_to_process: Vec<_> = issues.all().values().cloned().collect();
+        for deepness_level in 0..deepness {
+            slog_scope::info!("Fetching hierarchy at level {}", deepness_level + 1);
+            let mut new_issues_to_process = HashMap::new();
+            let mut issues_to_fetch = HashSet::new();
+            let mut epics_to_fetch = HashSet::new();
+
+            for issue in &issues_to_process {
+                for link in Self::hierarchy_links(issue) {
+                    for id in [&link.parent, &link.child] {
+                        if issues.all().contains_key(id) {
+                            continue;
+                        }
+                        if link.kind == HierarchyKind::Epic {
+                            let _ = epics_to_fetch.insert(id.clone());
+                        } else {
+                            let _ = issues_to_fetch.insert(id.clone());
+                        }
+                    }
+                    let _ = hierarchy.insert(link);
+                }
+            }
+
+            // Children referencing processed issues by "Parent Link" or epic link
+            let mut parents_by_jira: HashMap<_, Vec<_>> = HashMap::new();
+            for issue in &issues_to_process {
+                parents_by_jira.entry(&issue.jira).or_default().push(issue);
+            }
+            for (jira, parents) in parents_by_jira {
+                let mut conditions = Vec::new();
+                if let Some(parent_link) = &jira.custom_fields.parent_link {
+                    conditions.push(format!(
+                        "{} in ({})",
+                        parent_link.jql_name(),
+                        parents
+                            .iter()
+                            .map(|v| v.issue.key.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))
+                }
+                let parent_epics: Vec<_> = parents
+                    .iter()
+                    .filter(|v| epics.get(jira, &v.issue.key).is_some())
+                    .map(|v| v.issue.key.as_str())
+                    .collect();
+                if let (false, Some(epic_link)) =
+                    (parent_epics.is_empty(), &jira.custom_fields.epic_link)
+                {
+                    conditions.push(format!(
+                        "{} in ({})",
+                        epic_link.jql_name(),
+                        parent_epics.join(", ")
+                    ))
+                }
+                if conditions.is_empty() {
+                    continue;
+                }
+                let children = Self::search_issues(
+                    jira,
+                    &conditions.join(" OR "),
+                    crate::report::ReportIssueType::Hierarchy,
+                )
+                .await?;
+                for child in children {
+                    let id = IssueID::of_issue(&child);
+                    hierarchy.extend(
+                        Self::hierarchy_links(&child)
+                            .into_iter()
+                            .filter(|link| link.child == id),
+                    );
+                    if !issues.all().contains_key(&id) {
+                        issues.insert(&child);
+                        let _ = new_issues_to_process.insert(id, child);
+                    }
+                }
+            }
+
+            for (ids, entity_type) in [
+                (issues_to_fetch, crate::report::ReportIssueType::Hierarchy),
+                (epics_to_fetch, crate::report::ReportIssueType::Epic),
+            ] {
+                let mut fetch_by_jira: HashMap<_, Vec<_>> = HashMap::new();
+                for id in ids.iter() {
+                    fetch_by_jira.entry(&id.jira).or_default().push(&id.issue);
+                }
+                for (jira, keys) in fetch_by_jira {
+                    let jql = keys
+                        .iter()
+                        .map(|key| format!("key = {}", key))
+                        .collect::<Vec<_>>()
+                        .join(" OR ");
+                    for issue in Self::search_issues(jira, &jql, entity_type.clone()).await? {
+                        issues.insert(&issue);
+                        if entity_type == crate::report::ReportIssueType::Epic {
+                            epics.insert(&issue);
+                        }
+                        let _ = new_issues_to_process.insert(IssueID::of_issue(&issue), issue);
+                    }
+                }
+            }
+
+            issues_to_process = new_issues_to_process.into_values().collect()
+        }
+
+        // Issues could be fetched after the relation was found, keep only known ones
+        hierarchy.retain(|v| {
+            issues.all().contains_key(&v.parent) && issues.all().contains_key(&v.child)
+        });
+        Ok(hierarchy)
+    }
+
     pub async fn of_slice(
         foreign_relations: &[crate::report::ForeignRelation],
         slice: &[crate::report::ReportIssue],
         dependencies_deepness: usize,
+        hierarchy_deepness: usize,
     ) -> Result<Self> {
         let mut issues = IssuesList::of_slice(slice);
         let relations =
             Self::get_relations(foreign_relations, &mut issues, dependencies_deepness).await?;
-        let epics = Self::get_epics(&mut issues).await?;
+        let mut epics = Self::get_epics(&mut issues).await?;
+        let hierarchy = Self::get_hierarchy(&mut issues, &mut epics, hierarchy_deepness).await?;
         Ok(Self {
             issues,
             epics,
             relations,
+            hierarchy,
         })
     }
 }
+
+/// Hand-built issues and relations for tests of report algorithms
+#[cfg(test)]
+pub mod test_data {
+    use std::collections::HashSet;
+
+    use super::{IssueID, IssuesList, Relation, ReportData};
+    use crate::report::{ReportIssue, ReportIssueType};
+
+    pub fn jira() -> crate::jira::JiraServer {
+        serde_yaml::from_str(
+            "name: test\nbase_url: https://jira.test/\naccess:\n  Token:\n    String: token\n",
+        )
+        .unwrap()
+    }
+
+    pub fn id(key: &str) -> IssueID {
+        IssueID::new(&jira(), key)
+    }
+
+    /// Issue with planned dates `YYYY-MM-DD` and resolution moment like
+    /// `2022-10-05T10:00:00.000+0000`
+    pub fn issue(
+        key: &str,
+        planned_start: Option<&str>,
+        planned_end: Option<&str>,
+        resolved: Option<&str>,
+    ) -> ReportIssue {
+        let bean = serde_json::json!({
+            "id": key,
+            "key": key,
+            "self": format!("https://jira.test/rest/api/2/issue/{}", key),
+            "fields": {
+                "summary": format!("Summary of {}", key),
+                "description": null,
+                "created": "2022-10-01T10:00:00.000+0000",
+                "creator": {"displayName": "Creator"},
+                "resolutiondate": resolved,
+                "votes": {},
+                "watches": {},
+            },
+        });
+        let bean =
+            crate::jira_types::IssueBean::of_json(serde_json::from_value(bean).unwrap()).unwrap();
+        let jira = jira();
+        let mut issue =
+            ReportIssue::of_issuebean(&jira, &bean, ReportIssueType::ReportMember).unwrap();
+        issue.custom_fields.planned_start = planned_start.and_then(|v| jira.dates.parse(v));
+        issue.custom_fields.planned_end = planned_end.and_then(|v| jira.dates.parse(v));
+        issue
+    }
+
+    /// Report data with ordering relations between issues by their keys, the first issue of a
+    /// pair should be finished before the second one starts
+    pub fn data(issues: &[ReportIssue], relations: &[(&str, &str)]) -> ReportData {
+        let kind = crate::report::default_relation_kinds()
+            .into_iter()
+            .find(|v| v.ordering)
+            .unwrap();
+        ReportData {
+            issues: IssuesList::of_slice(issues),
+            epics: IssuesList::new(),
+            relations: relations
+                .iter()
+                .map(|(from, to)| Relation {
+                    from: id(from),
+                    to: id(to),
+                    kind: kind.clone(),
+                })
+                .collect(),
+            hierarchy: HashSet::new(),
+        }
+    }
+}
@@ -1,10 +1,233 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{bail, format_err, Result};
 use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum GraphFormat {
+    /// Rendered by Graphviz
+    #[default]
+    Svg,
+    /// Rendered by Graphviz
+    Png,
+    /// Graphviz source
+    Dot,
+    Mermaid,
+    PlantUml,
+    /// List of nodes and edges
+    Json,
+}
+
+impl GraphFormat {
+    pub fn extension(&self) -> &str {
+        match self {
+            GraphFormat::Svg => "svg",
+            GraphFormat::Png => "png",
+            GraphFormat::Dot => "dot",
+            GraphFormat::Mermaid => "mmd",
+            GraphFormat::PlantUml => "puml",
+            GraphFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "svg" => Ok(Self::Svg),
+            "png" => Ok(Self::Png),
+            "dot" => Ok(Self::Dot),
+            "mermaid" | "mmd" => Ok(Self::Mermaid),
+            "plantuml" | "puml" => Ok(Self::PlantUml),
+            "json" => Ok(Self::Json),
+            _ => Err("Unknown graph format".to_owned()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonNode {
+    id: String,
+    key: String,
+    jira: url::Url,
+    url: url::Url,
+    summary: String,
+    status: Option<String>,
+    assignee: Option<String>,
+    epic: Option<String>,
+    external: bool,
+    planned_start: Option<chrono::DateTime<chrono::Utc>>,
+    planned_end: Option<chrono::DateTime<chrono::Utc>>,
+    critical: bool,
+}
+
+#[derive(Serialize)]
+struct JsonEdge {
+    from: String,
+    to: String,
+    kind: String,
+    critical: bool,
+    cycle: bool,
+    schedule_problem: bool,
+}
+
+#[derive(Serialize)]
+struct JsonHierarchyEdge {
+    parent: String,
+    child: String,
+    kind: crate::report_data::HierarchyKind,
+}
+
+#[derive(Serialize)]
+struct JsonGraph {
+    nodes: Vec<JsonNode>,
+    edges: Vec<JsonEdge>,
+    hierarchy: Vec<JsonHierarchyEdge>,
+}
+
+/// Decorations of the graph calculated by analysis of the report data
+#[derive(Default)]
+pub struct GraphHighlights {
+    /// Issues of the critical path
+    pub critical_issues: HashSet<crate::report_data::IssueID>,
+    /// Relations of the critical path as (from, to) pairs
+    pub critical_relations: HashSet<(crate::report_data::IssueID, crate::report_data::IssueID)>,
+    /// Relations forming dependency cycles as (from, to) pairs
+    pub cycle_relations: HashSet<(crate::report_data::IssueID, crate::report_data::IssueID)>,
+    /// Relations with inconsistent schedule as (from, to) pairs
+    pub schedule_relations: HashSet<(crate::report_data::IssueID, crate::report_data::IssueID)>,
+}
+
+impl GraphHighlights {
+    pub fn of_critical_path(result: &crate::report_critical_path::CriticalPathResult) -> Self {
+        Self {
+            critical_issues: result
+                .timings
+                .iter()
+                .filter(|(_, v)| v.is_critical())
+                .map(|(id, _)| id.clone())
+                .collect(),
+            critical_relations: result.relations.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_cycles(self, cycles: &crate::report_cycles::Cycles) -> Self {
+        Self {
+            cycle_relations: cycles.relations.clone(),
+            ..self
+        }
+    }
+
+    pub fn with_schedule(self, schedule: &crate::report_schedule::ScheduleCheck) -> Self {
+        Self {
+            schedule_relations: schedule.relations.clone(),
+            ..self
+        }
+    }
+
+    fn is_critical_issue(&self, issue: &crate::report::ReportIssue) -> bool {
+        self.critical_issues
+            .contains(&crate::report_data::IssueID::of_issue(issue))
+    }
+
+    fn is_critical_relation(&self, relation: &crate::report_data::Relation) -> bool {
+        self.critical_relations
+            .contains(&(relation.from.clone(), relation.to.clone()))
+    }
+
+    fn is_cycle_relation(&self, relation: &crate::report_data::Relation) -> bool {
+        self.cycle_relations
+            .contains(&(relation.from.clone(), relation.to.clone()))
+    }
+
+    fn is_schedule_relation(&self, relation: &crate::report_data::Relation) -> bool {
+        self.schedule_relations
+            .contains(&(relation.from.clone(), relation.to.clone()))
+    }
+}
+
+const CRITICAL_COLOR: &str = "#D50000";
+const CYCLE_COLOR: &str = "#AA00FF";
+const SCHEDULE_COLOR: &str = "#FF6D00";
+const HIERARCHY_COLOR: &str = "#6B778C";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum GraphClusterBy {
+    #[default]
+    Epic,
+    /// Jira instance of the issue
+    Jira,
+    Assignee,
+    /// Issues are not clustered
+    None,
+}
+
+impl FromStr for GraphClusterBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "epic" => Ok(Self::Epic),
+            "jira" => Ok(Self::Jira),
+            "assignee" => Ok(Self::Assignee),
+            "none" => Ok(Self::None),
+            _ => Err("Unknown graph clustering".to_owned()),
+        }
+    }
+}
+
+/// Group of issues drawn as a subgraph
+#[derive(Hash, PartialEq, Eq)]
+struct GraphCluster {
+    key: String,
+    label: String,
+    url: Option<url::Url>,
+}
+
+fn default_neighbourhood_depth() -> usize {
+    1
+}
+
 #[derive(Serialize, Deserialize, Clone)]
-pub struct DependencyGraph;
+pub struct DependencyGraph {
+    #[serde(default)]
+    pub format: GraphFormat,
+    /// Hide issues with status of "done" category
+    #[serde(default)]
+    pub hide_done: bool,
+    /// Names of relation kinds to draw. All kinds are drawn if empty
+    #[serde(default)]
+    pub relation_kinds: Vec<String>,
+    /// Draw each epic as a single node with relations of its issues
+    #[serde(default)]
+    pub collapse_epics: bool,
+    /// Keys of issues to draw with their neighbours only. Whole graph is drawn if empty
+    #[serde(default)]
+    pub neighbourhood: Vec<String>,
+    /// Number of relations between `neighbourhood` issues and drawn neighbours
+    #[serde(default = "default_neighbourhood_depth")]
+    pub neighbourhood_depth: usize,
+    #[serde(default)]
+    pub cluster_by: GraphClusterBy,
+}
+
+impl Default for DependencyGraph {
+    fn default() -> Self {
+        Self {
+            format: GraphFormat::default(),
+            hide_done: false,
+            relation_kinds: Vec::new(),
+            collapse_epics: false,
+            neighbourhood: Vec::new(),
+            neighbourhood_depth: default_neighbourhood_depth(),
+            cluster_by: GraphClusterBy::default(),
+        }
+    }
+}
 
 impl DependencyGraph {
     fn issue_id(issue: &crate::report::ReportIssue) -> String {
@@ -21,7 +244,11 @@ impl DependencyGraph {
             .replace('>', "&gt;")
     }
 
-    pub fn generate_dot(&self, data: &crate::report_data::ReportData) -> Result<String> {
+    pub fn generate_dot(
+        &self,
+        data: &crate::report_data::ReportData,
+        highlights: &GraphHighlights,
+    ) -> Result<String> {
         use std::fmt::Write;
 
         let mut output = String::new();
@@ -30,62 +257,32 @@ impl DependencyGraph {
         writeln!(&mut output, "node [style=filled, shape=box]")?;
         writeln!(&mut output, "edge [penwidth=2]")?;
 
-        let issues_epics: HashSet<_> = data
-            .issues
-            .all()
-            .values()
-            .map(|issue| (&issue.jira, &issue.custom_fields.epic_link))
-            .collect();
-
-        for (cluster_id, (jira, epic_link)) in issues_epics.iter().enumerate() {
-            let epic_issues =
-                data.issues.all().values().filter(|issue| {
-                    (&issue.jira, &issue.custom_fields.epic_link) == (jira, epic_link)
-                });
-
-            let epic = match &epic_link {
-                None => None,
-                Some(epic_link) => {
-                    slog_scope::debug!("Searching for epic {}", &epic_link);
-                    let epic_id = crate::report_data::IssueID::new(jira, epic_link);
-                    data.epics.all().get(&epic_id)
-                }
-            };
-
-            if let Some(epic) = epic {
-                let epic_summary = epic.issue.fields.summary.as_str();
-                let epic_href = format!("; href=\"{}\"", epic.url());
+        for (cluster_id, (cluster, issues)) in self.clusters(data).iter().enumerate() {
+            if let Some(cluster) = cluster {
+                let href = match &cluster.url {
+                    None => String::new(),
+                    Some(v) => format!("; href=\"{}\"", Self::double_string_escape(v.as_str())),
+                };
                 writeln!(
                     &mut output,
-                    " subgraph cluster_{} {{ style=filled; color=\"#C0D5FF\"; label=\"ЭПИК: {}\"{}",
-                    cluster_id, epic_summary, epic_href
+                    " subgraph cluster_{} {{ style=filled; color=\"#C0D5FF\"; label=\"{}\"{}",
+                    cluster_id,
+                    Self::double_string_escape(&cluster.label),
+                    href
                 )?;
             }
 
-            for issue in epic_issues {
+            for issue in issues {
                 let duration = if issue.custom_fields.planned_start.is_some()
                     || issue.custom_fields.planned_end.is_some()
                 {
-                    format!("<br/>План: {}", issue.custom_fields.plan())
+                    format!("<br/>План: {}", issue.custom_fields.plan(&issue.jira.dates))
                 } else {
                     "".to_owned()
                 };
-                let duration = {
-                    let planned_end = issue
-                        .custom_fields
-                        .planned_end
-                        .unwrap_or_else(|| chrono::Utc::now() + chrono::Duration::days(100000));
-                    let planned_start = issue
-                        .custom_fields
-                        .planned_start
-                        .unwrap_or_else(|| chrono::Utc::now() + chrono::Duration::days(100000));
-                    if planned_end - chrono::Duration::days(3) < chrono::Utc::now() {
-                        format!("<font color=\"red\">{}</font>", duration)
-                    } else if planned_start - chrono::Duration::days(3) < chrono::Utc::now() {
-                        format!("<font color=\"green\">{}</font>", duration)
-                    } else {
-                        duration
-                    }
+                let duration = match issue.custom_fields.plan_color(&issue.jira.dates) {
+                    None => duration,
+                    Some(color) => format!("<font color=\"{}\">{}</font>", color, duration),
                 };
 
                 let assignee = match &issue.issue.fields.assignee {
@@ -122,11 +319,18 @@ impl DependencyGraph {
                     _ => "red",
                 };
 
+                let highlight = if highlights.is_critical_issue(issue) {
+                    format!(";color=\"{}\";penwidth=4", CRITICAL_COLOR)
+                } else {
+                    "".to_owned()
+                };
+
                 match issue.entity_type {
                     crate::report::ReportIssueType::ReportMember => writeln!(
                         output,
-                        "    {} [fillcolor=\"#8CB3FF\";label=<{}{}{}<i><font color=\"{}\">{}</font></i>>;href=\"{}\"]",
+                        "    {} [fillcolor=\"#8CB3FF\"{};label=<{}{}{}<i><font color=\"{}\">{}</font></i>>;href=\"{}\"]",
                         Self::issue_id(issue),
+                        highlight,
                         &Self::html_string_escape(&issue.issue.fields.summary),
                         &assignee,
                         &duration,
@@ -136,8 +340,9 @@ impl DependencyGraph {
                     )?,
                     crate::report::ReportIssueType::ExternalDependency => writeln!(
                         output,
-                        "    {} [fillcolor=\"#80FFD2\";href=\"{}\";label=<Внешняя задача<br/>{}{}{}<i><font color=\"{}\">{}</font></i>>]",
+                        "    {} [fillcolor=\"#80FFD2\"{};href=\"{}\";label=<Внешняя задача<br/>{}{}{}<i><font color=\"{}\">{}</font></i>>]",
                         Self::issue_id(issue),
+                        highlight,
                         Self::double_string_escape(issue.url().as_ref()),
                         &Self::html_string_escape(&issue.issue.fields.summary),
                         &assignee,
@@ -145,27 +350,80 @@ impl DependencyGraph {
                         status_color,
                         &status,
                     )?,
-                    crate::report::ReportIssueType::Epic => (),
+                    crate::report::ReportIssueType::Hierarchy
+                    | crate::report::ReportIssueType::Epic => writeln!(
+                        output,
+                        "    {} [fillcolor=\"{}\"{};href=\"{}\";label=<{}{}{}{}<i><font color=\"{}\">{}</font></i>>]",
+                        Self::issue_id(issue),
+                        Self::node_color(issue),
+                        highlight,
+                        Self::double_string_escape(issue.url().as_ref()),
+                        if issue.entity_type == crate::report::ReportIssueType::Epic {
+                            "Эпик<br/>"
+                        } else {
+                            ""
+                        },
+                        &Self::html_string_escape(&issue.issue.fields.summary),
+                        &assignee,
+                        &duration,
+                        status_color,
+                        &status,
+                    )?,
                 }
             }
 
-            if epic.is_some() {
+            if cluster.is_some() {
                 writeln!(&mut output, "  }}")?;
             }
         }
 
-        for relation in &data.relations {
-            let style_attrs = match relation.kind {
-                crate::report::RelationKind::Dependance => "color=\"#2E56A6\", style=solid",
-                crate::report::RelationKind::Block => "color=\"#A65229\", style=bold",
-                crate::report::RelationKind::Mention => "color=\"#7F94BF\", style=dashed",
+        for relation in Self::sorted_relations(data) {
+            let style = &relation.kind.style;
+            let mut style_attrs = format!(
+                "color=\"{}\", style={}",
+                Self::double_string_escape(&style.color),
+                match style.line {
+                    crate::report::LineStyle::Solid => "solid",
+                    crate::report::LineStyle::Bold => "bold",
+                    crate::report::LineStyle::Dashed => "dashed",
+                    crate::report::LineStyle::Dotted => "dotted",
+                }
+            );
+            if let Some(label) = &style.label {
+                write!(
+                    &mut style_attrs,
+                    ", label=\"{}\"",
+                    Self::double_string_escape(label)
+                )?
+            }
+            if relation.kind.direction == crate::report::RelationDirection::Undirected {
+                write!(&mut style_attrs, ", dir=none")?
+            }
+            let highlight = if highlights.is_critical_relation(relation) {
+                format!(", color=\"{}\", penwidth=4", CRITICAL_COLOR)
+            } else if highlights.is_cycle_relation(relation) {
+                format!(", color=\"{}\", style=dashed, penwidth=3", CYCLE_COLOR)
+            } else if highlights.is_schedule_relation(relation) {
+                format!(", color=\"{}\", penwidth=3", SCHEDULE_COLOR)
+            } else {
+                "".to_owned()
             };
             writeln!(
                 &mut output,
-                "{} -> {} [{}]",
+                "{} -> {} [{}{}]",
                 relation.from.as_string(),
                 relation.to.as_string(),
-                style_attrs
+                style_attrs,
+                highlight
+            )?;
+        }
+        for relation in self.sorted_hierarchy(data) {
+            writeln!(
+                &mut output,
+                "{} -> {} [color=\"{}\", style=dashed, dir=back, arrowtail=diamond]",
+                relation.parent.as_string(),
+                relation.child.as_string(),
+                HIERARCHY_COLOR
             )?;
         }
         writeln!(&mut output, "}}")?;
@@ -173,33 +431,778 @@ impl DependencyGraph {
         Ok(output)
     }
 
-    pub fn make(&self, data: &crate::report_data::ReportData) -> Result<tempfile::NamedTempFile> {
-        use std::io::Write;
+    fn epic<'a>(
+        data: &'a crate::report_data::ReportData,
+        issue: &crate::report::ReportIssue,
+    ) -> Option<&'a crate::report::ReportIssue> {
+        issue
+            .custom_fields
+            .epic_link
+            .as_ref()
+            .and_then(|epic_link| {
+                data.epics
+                    .all()
+                    .get(&crate::report_data::IssueID::new(&issue.jira, epic_link))
+            })
+    }
+
+    fn cluster(
+        &self,
+        data: &crate::report_data::ReportData,
+        issue: &crate::report::ReportIssue,
+    ) -> Option<GraphCluster> {
+        match self.cluster_by {
+            GraphClusterBy::Epic => Self::epic(data, issue).map(|epic| GraphCluster {
+                key: crate::report_data::IssueID::of_issue(epic).as_string(),
+                label: format!("ЭПИК: {}", epic.issue.fields.summary),
+                url: Some(epic.url()),
+            }),
+            GraphClusterBy::Jira => Some(GraphCluster {
+                key: issue.jira.base_url.to_string(),
+                label: format!("Jira: {}", issue.jira.base_url),
+                url: Some(issue.jira.base_url.clone()),
+            }),
+            GraphClusterBy::Assignee => issue
+                .issue
+                .fields
+                .assignee
+                .as_ref()
+                .and_then(|v| v.display_name.as_ref())
+                .map(|name| GraphCluster {
+                    key: name.clone(),
+                    label: format!("Исполнитель: {}", name),
+                    url: None,
+                }),
+            GraphClusterBy::None => None,
+        }
+    }
+
+    /// Drawn issues grouped by `cluster_by`. Cluster is `None` for issues outside of clusters
+    fn clusters<'a>(
+        &self,
+        data: &'a crate::report_data::ReportData,
+    ) -> Vec<(Option<GraphCluster>, Vec<&'a crate::report::ReportIssue>)> {
+        let mut groups: HashMap<_, Vec<_>> = HashMap::new();
+        for issue in data.issues.all().values() {
+            if self.is_node(data, issue) {
+                groups
+                    .entry(self.cluster(data, issue))
+                    .or_default()
+                    .push(issue)
+            }
+        }
+
+        let mut groups: Vec<_> = groups
+            .into_iter()
+            .map(|(cluster, mut issues)| {
+                issues.sort_by(|a, b| a.issue.key.cmp(&b.issue.key));
+                (cluster, issues)
+            })
+            .collect();
+        groups.sort_by_key(|(cluster, issues)| {
+            (
+                cluster.as_ref().map(|v| v.key.clone()),
+                issues.first().map(|v| v.issue.key.clone()),
+            )
+        });
+        groups
+    }
+
+    /// Epics are drawn as clusters, and as nodes only when they have a "Parent Link" parent or
+    /// epics are collapsed
+    fn is_node(
+        &self,
+        data: &crate::report_data::ReportData,
+        issue: &crate::report::ReportIssue,
+    ) -> bool {
+        if self.collapse_epics || issue.entity_type != crate::report::ReportIssueType::Epic {
+            return true;
+        }
+        let id = crate::report_data::IssueID::of_issue(issue);
+        data.hierarchy
+            .iter()
+            .any(|v| v.kind == crate::report_data::HierarchyKind::ParentLink && v.child == id)
+    }
+
+    /// Hierarchy relations between drawn nodes. Epic links are shown by clusters instead
+    fn sorted_hierarchy<'a>(
+        &self,
+        data: &'a crate::report_data::ReportData,
+    ) -> Vec<&'a crate::report_data::HierarchyRelation> {
+        let is_node = |id| {
+            data.issues
+                .all()
+                .get(id)
+                .map(|issue| self.is_node(data, issue))
+                .unwrap_or_default()
+        };
+        let mut relations: Vec<_> = data
+            .hierarchy
+            .iter()
+            .filter(|v| {
+                v.kind != crate::report_data::HierarchyKind::Epic
+                    && is_node(&v.parent)
+                    && is_node(&v.child)
+            })
+            .collect();
+        relations.sort_by_key(|v| (v.parent.as_string(), v.child.as_string()));
+        relations
+    }
+
+    fn is_done(issue: &crate::report::ReportIssue) -> bool {
+        issue
+            .issue
+            .fields
+            .status
+            .as_ref()
+            .and_then(|v| v.status_category.as_ref())
+            .and_then(|v| v.key.as_deref())
+            == Some("done")
+    }
+
+    /// Issues within `neighbourhood_depth` relations from `neighbourhood` issues
+    fn neighbourhood(
+        &self,
+        data: &crate::report_data::ReportData,
+        relations: &[&crate::report_data::Relation],
+        visible: &HashSet<crate::report_data::IssueID>,
+    ) -> HashSet<crate::report_data::IssueID> {
+        let mut neighbours: HashMap<_, Vec<_>> = HashMap::new();
+        let edges = relations
+            .iter()
+            .map(|v| (&v.from, &v.to))
+            .chain(data.hierarchy.iter().map(|v| (&v.parent, &v.child)));
+        for (a, b) in edges {
+            if visible.contains(a) && visible.contains(b) {
+                neighbours.entry(a).or_default().push(b);
+                neighbours.entry(b).or_default().push(a);
+            }
+        }
+
+        let mut result: HashSet<_> = HashSet::new();
+        for key in &self.neighbourhood {
+            let found: Vec<_> = visible.iter().filter(|v| &v.issue == key).collect();
+            if found.is_empty() {
+                slog_scope::warn!("Issue {} of graph neighbourhood is not found", key);
+            }
+            result.extend(found);
+        }
+        let mut layer: Vec<_> = result.iter().copied().collect();
+        for _ in 0..self.neighbourhood_depth {
+            layer = layer
+                .into_iter()
+                .flat_map(|id| neighbours.get(id).cloned().unwrap_or_default())
+                .filter(|id| result.insert(*id))
+                .collect();
+        }
+        result.into_iter().cloned().collect()
+    }
+
+    /// Report data and highlights reduced to drawn issues and relations. Issues of collapsed
+    /// epics are replaced by their epics
+    fn view(
+        &self,
+        data: &crate::report_data::ReportData,
+        highlights: &GraphHighlights,
+    ) -> (crate::report_data::ReportData, GraphHighlights) {
+        let relations: Vec<_> = data
+            .relations
+            .iter()
+            .filter(|v| {
+                self.relation_kinds.is_empty() || self.relation_kinds.contains(&v.kind.name)
+            })
+            .collect();
+
+        let mut visible: HashSet<_> = data
+            .issues
+            .all()
+            .iter()
+            .filter(|(_, issue)| !(self.hide_done && Self::is_done(issue)))
+            .map(|(id, _)| id.clone())
+            .collect();
+        if !self.neighbourhood.is_empty() {
+            visible = self.neighbourhood(data, &relations, &visible);
+        }
+
+        let mut issues = crate::report_data::IssuesList::new();
+        let mut mapping = HashMap::new();
+        for id in &visible {
+            let issue = &data.issues.all()[id];
+            let node = match Self::epic(data, issue).filter(|_| self.collapse_epics) {
+                None => issue,
+                Some(epic) => epic,
+            };
+            let node_id = crate::report_data::IssueID::of_issue(node);
+            if !issues.all().contains_key(&node_id) {
+                issues.insert(node)
+            }
+            let _ = mapping.insert(id.clone(), node_id);
+        }
+        let map = |(from, to): (&crate::report_data::IssueID, &crate::report_data::IssueID)| {
+            match (mapping.get(from), mapping.get(to)) {
+                // Relations inside collapsed epics are hidden, but self-relations are kept
+                (Some(a), Some(b)) if a != b || from == to => Some((a.clone(), b.clone())),
+                _ => None,
+            }
+        };
+
+        let relations = relations
+            .into_iter()
+            .filter_map(|v| {
+                map((&v.from, &v.to)).map(|(from, to)| crate::report_data::Relation {
+                    from,
+                    to,
+                    kind: v.kind.clone(),
+                })
+            })
+            .collect();
+        let hierarchy = data
+            .hierarchy
+            .iter()
+            .filter(|v| !self.collapse_epics || v.kind != crate::report_data::HierarchyKind::Epic)
+            .filter_map(|v| {
+                map((&v.parent, &v.child)).map(|(parent, child)| {
+                    crate::report_data::HierarchyRelation {
+                        parent,
+                        child,
+                        kind: v.kind,
+                    }
+                })
+            })
+            .collect();
+        let map_pairs = |pairs: &HashSet<(_, _)>| {
+            pairs
+                .iter()
+                .filter_map(|(from, to)| map((from, to)))
+                .collect()
+        };
+
+        let view_highlights = GraphHighlights {
+            critical_issues: highlights
+                .critical_issues
+                .iter()
+                .filter_map(|v| mapping.get(v).cloned())
+                .collect(),
+            critical_relations: map_pairs(&highlights.critical_relations),
+            cycle_relations: map_pairs(&highlights.cycle_relations),
+            schedule_relations: map_pairs(&highlights.schedule_relations),
+        };
+        let view = crate::report_data::ReportData {
+            issues,
+            epics: data.epics.clone(),
+            relations,
+            hierarchy,
+        };
+        (view, view_highlights)
+    }
+
+    /// Plain text lines of the node label
+    fn node_label(issue: &crate::report::ReportIssue) -> Vec<String> {
+        let mut label = Vec::new();
+        match issue.entity_type {
+            crate::report::ReportIssueType::ExternalDependency => {
+                label.push("Внешняя задача".to_owned())
+            }
+            crate::report::ReportIssueType::Epic => label.push("Эпик".to_owned()),
+            _ => (),
+        }
+        label.push(issue.issue.fields.summary.clone());
+        if let Some(v) = issue
+            .issue
+            .fields
+            .assignee
+            .as_ref()
+            .and_then(|v| v.display_name.as_ref())
+        {
+            label.push(format!("Исполнитель {}", v))
+        }
+        if issue.custom_fields.planned_start.is_some() || issue.custom_fields.planned_end.is_some()
+        {
+            label.push(format!(
+                "План: {}",
+                issue.custom_fields.plan(&issue.jira.dates)
+            ))
+        }
+        if let Some(v) = issue
+            .issue
+            .fields
+            .status
+            .as_ref()
+            .and_then(|v| v.name.as_ref())
+        {
+            label.push(v.clone())
+        }
+        label
+    }
+
+    fn node_color(issue: &crate::report::ReportIssue) -> &str {
+        match issue.entity_type {
+            crate::report::ReportIssueType::ExternalDependency => "#80FFD2",
+            crate::report::ReportIssueType::Hierarchy => "#D5C2FF",
+            crate::report::ReportIssueType::Epic => "#C0D5FF",
+            _ => "#8CB3FF",
+        }
+    }
+
+    fn sorted_relations(
+        data: &crate::report_data::ReportData,
+    ) -> Vec<&crate::report_data::Relation> {
+        let mut relations: Vec<_> = data.relations.iter().collect();
+        relations.sort_by(|a, b| {
+            (a.from.as_string(), a.to.as_string(), &a.kind.name).cmp(&(
+                b.from.as_string(),
+                b.to.as_string(),
+                &b.kind.name,
+            ))
+        });
+        relations
+    }
+
+    fn mermaid_escape(s: &str) -> String {
+        s.replace('&', "#amp;")
+            .replace('"', "#quot;")
+            .replace('<', "#lt;")
+            .replace('>', "#gt;")
+    }
+
+    pub fn generate_mermaid(
+        &self,
+        data: &crate::report_data::ReportData,
+        highlights: &GraphHighlights,
+    ) -> Result<String> {
+        use std::fmt::Write;
+
+        let mut output = String::new();
+        writeln!(&mut output, "flowchart LR")?;
+        writeln!(&mut output, "  classDef member fill:#8CB3FF")?;
+        writeln!(&mut output, "  classDef external fill:#80FFD2")?;
+        writeln!(&mut output, "  classDef hierarchy fill:#D5C2FF")?;
+        writeln!(&mut output, "  classDef epic fill:#C0D5FF")?;
+        writeln!(
+            &mut output,
+            "  classDef critical stroke:{},stroke-width:4px",
+            CRITICAL_COLOR
+        )?;
+
+        for (cluster_id, (cluster, issues)) in self.clusters(data).iter().enumerate() {
+            if let Some(cluster) = cluster {
+                writeln!(
+                    &mut output,
+                    "  subgraph cluster_{}[\"{}\"]",
+                    cluster_id,
+                    Self::mermaid_escape(&cluster.label)
+                )?;
+            }
+            for issue in issues {
+                let label = Self::node_label(issue)
+                    .iter()
+                    .map(|v| Self::mermaid_escape(v))
+                    .collect::<Vec<_>>()
+                    .join("<br/>");
+                let class = match issue.entity_type {
+                    crate::report::ReportIssueType::ExternalDependency => "external",
+                    crate::report::ReportIssueType::Hierarchy => "hierarchy",
+                    crate::report::ReportIssueType::Epic => "epic",
+                    _ => "member",
+                };
+                let class = if highlights.is_critical_issue(issue) {
+                    format!("{},critical", class)
+                } else {
+                    class.to_owned()
+                };
+                writeln!(
+                    &mut output,
+                    "    {}[\"{}\"]:::{}",
+                    Self::issue_id(issue),
+                    label,
+                    class
+                )?;
+                writeln!(
+                    &mut output,
+                    "    click {} href \"{}\" _blank",
+                    Self::issue_id(issue),
+                    issue.url()
+                )?;
+            }
+            if cluster.is_some() {
+                writeln!(&mut output, "  end")?;
+            }
+        }
+
+        for (n, relation) in Self::sorted_relations(data).iter().enumerate() {
+            let directed = relation.kind.direction == crate::report::RelationDirection::Directed;
+            let arrow = match (relation.kind.style.line, directed) {
+                (crate::report::LineStyle::Solid, true) => "-->",
+                (crate::report::LineStyle::Solid, false) => "---",
+                (crate::report::LineStyle::Bold, true) => "==>",
+                (crate::report::LineStyle::Bold, false) => "===",
+                (_, true) => "-.->",
+                (_, false) => "-.-",
+            };
+            let label = match &relation.kind.style.label {
+                None => String::new(),
+                Some(v) => format!("|\"{}\"|", Self::mermaid_escape(v)),
+            };
+            writeln!(
+                &mut output,
+                "  {} {}{} {}",
+                relation.from.as_string(),
+                arrow,
+                label,
+                relation.to.as_string()
+            )?;
+            if highlights.is_critical_relation(relation) {
+                writeln!(
+                    &mut output,
+                    "  linkStyle {} stroke:{},stroke-width:4px",
+                    n, CRITICAL_COLOR
+                )?;
+            } else if highlights.is_cycle_relation(relation) {
+                writeln!(
+                    &mut output,
+                    "  linkStyle {} stroke:{},stroke-width:3px,stroke-dasharray:5 5",
+                    n, CYCLE_COLOR
+                )?;
+            } else if highlights.is_schedule_relation(relation) {
+                writeln!(
+                    &mut output,
+                    "  linkStyle {} stroke:{},stroke-width:3px",
+                    n, SCHEDULE_COLOR
+                )?;
+            } else {
+                writeln!(
+                    &mut output,
+                    "  linkStyle {} stroke:{}",
+                    n, relation.kind.style.color
+                )?;
+            }
+        }
 
-        let dotfile_content = self.generate_dot(data)?;
+        let relations_count = Self::sorted_relations(data).len();
+        for (n, relation) in self.sorted_hierarchy(data).iter().enumerate() {
+            writeln!(
+                &mut output,
+                "  {} --o {}",
+                relation.parent.as_string(),
+                relation.child.as_string()
+            )?;
+            writeln!(
+                &mut output,
+                "  linkStyle {} stroke:{},stroke-dasharray:5 5",
+                relations_count + n,
+                HIERARCHY_COLOR
+            )?;
+        }
 
-        let mut dotfile = tempfile::NamedTempFile::new()?;
-        dotfile.write_all(dotfile_content.as_bytes())?;
+        Ok(output)
+    }
 
-        let svg_file = tempfile::NamedTempFile::new()?;
+    fn plantuml_escape(s: &str) -> String {
+        s.replace('"', "'").replace(['\r', '\n'], " ")
+    }
+
+    pub fn generate_plantuml(
+        &self,
+        data: &crate::report_data::ReportData,
+        highlights: &GraphHighlights,
+    ) -> Result<String> {
+        use std::fmt::Write;
+
+        let mut output = String::new();
+        writeln!(&mut output, "@startuml")?;
+        writeln!(&mut output, "left to right direction")?;
 
-        std::process::Command::new("dot")
-            .args([
-                "-Tsvg",
-                "-o",
-                svg_file.path().to_str().unwrap(),
-                dotfile.path().to_str().unwrap(),
-            ])
-            .output()
+        for (cluster, issues) in self.clusters(data) {
+            if let Some(cluster) = &cluster {
+                let link = match &cluster.url {
+                    None => String::new(),
+                    Some(v) => format!(" [[{}]]", v),
+                };
+                writeln!(
+                    &mut output,
+                    "package \"{}\"{} #C0D5FF {{",
+                    Self::plantuml_escape(&cluster.label),
+                    link
+                )?;
+            }
+            for issue in issues {
+                let label = Self::node_label(issue)
+                    .iter()
+                    .map(|v| Self::plantuml_escape(v))
+                    .collect::<Vec<_>>()
+                    .join("\\n");
+                let highlight = if highlights.is_critical_issue(issue) {
+                    format!(";line:{};line.bold", CRITICAL_COLOR.trim_start_matches('#'))
+                } else {
+                    "".to_owned()
+                };
+                writeln!(
+                    &mut output,
+                    "  rectangle \"{}\" as {} {}{} [[{}]]",
+                    label,
+                    Self::issue_id(issue),
+                    Self::node_color(issue),
+                    highlight,
+                    issue.url()
+                )?;
+            }
+            if cluster.is_some() {
+                writeln!(&mut output, "}}")?;
+            }
+        }
+
+        for relation in Self::sorted_relations(data) {
+            let style = match relation.kind.style.line {
+                crate::report::LineStyle::Solid => "",
+                crate::report::LineStyle::Bold => ",bold",
+                crate::report::LineStyle::Dashed => ",dashed",
+                crate::report::LineStyle::Dotted => ",dotted",
+            };
+            let (color, style) = if highlights.is_critical_relation(relation) {
+                (CRITICAL_COLOR, ",bold")
+            } else if highlights.is_cycle_relation(relation) {
+                (CYCLE_COLOR, ",dashed")
+            } else if highlights.is_schedule_relation(relation) {
+                (SCHEDULE_COLOR, style)
+            } else {
+                (relation.kind.style.color.as_str(), style)
+            };
+            let head = match relation.kind.direction {
+                crate::report::RelationDirection::Directed => ">",
+                crate::report::RelationDirection::Undirected => "",
+            };
+            let label = match &relation.kind.style.label {
+                None => String::new(),
+                Some(v) => format!(" : {}", Self::plantuml_escape(v)),
+            };
+            writeln!(
+                &mut output,
+                "{} -[{}{}]-{} {}{}",
+                relation.from.as_string(),
+                color,
+                style,
+                head,
+                relation.to.as_string(),
+                label
+            )?;
+        }
+
+        for relation in self.sorted_hierarchy(data) {
+            writeln!(
+                &mut output,
+                "{} *-[{},dashed]- {}",
+                relation.parent.as_string(),
+                HIERARCHY_COLOR,
+                relation.child.as_string()
+            )?;
+        }
+
+        writeln!(&mut output, "@enduml")?;
+        Ok(output)
+    }
+
+    pub fn generate_json(
+        &self,
+        data: &crate::report_data::ReportData,
+        highlights: &GraphHighlights,
+    ) -> Result<String> {
+        let nodes = self
+            .clusters(data)
+            .into_iter()
+            .flat_map(|(_, issues)| {
+                issues.into_iter().map(|issue| JsonNode {
+                    id: Self::issue_id(issue),
+                    key: issue.issue.key.clone(),
+                    jira: issue.jira.base_url.clone(),
+                    url: issue.url(),
+                    summary: issue.issue.fields.summary.clone(),
+                    status: issue
+                        .issue
+                        .fields
+                        .status
+                        .as_ref()
+                        .and_then(|v| v.name.clone()),
+                    assignee: issue
+                        .issue
+                        .fields
+                        .assignee
+                        .as_ref()
+                        .and_then(|v| v.display_name.clone()),
+                    epic: Self::epic(data, issue).map(|v| v.issue.key.clone()),
+                    external: issue.entity_type
+                        == crate::report::ReportIssueType::ExternalDependency,
+                    planned_start: issue.custom_fields.planned_start,
+                    planned_end: issue.custom_fields.planned_end,
+                    critical: highlights.is_critical_issue(issue),
+                })
+            })
+            .collect();
+
+        let edges = Self::sorted_relations(data)
+            .into_iter()
+            .map(|relation| JsonEdge {
+                from: relation.from.as_string(),
+                to: relation.to.as_string(),
+                kind: relation.kind.name.clone(),
+                critical: highlights.is_critical_relation(relation),
+                cycle: highlights.is_cycle_relation(relation),
+                schedule_problem: highlights.is_schedule_relation(relation),
+            })
+            .collect();
+
+        let hierarchy = self
+            .sorted_hierarchy(data)
+            .into_iter()
+            .map(|relation| JsonHierarchyEdge {
+                parent: relation.parent.as_string(),
+                child: relation.child.as_string(),
+                kind: relation.kind,
+            })
+            .collect();
+
+        Ok(serde_json::to_string_pretty(&JsonGraph {
+            nodes,
+            edges,
+            hierarchy,
+        })?)
+    }
+
+    fn run_dot(&self, source: String) -> Result<Vec<u8>> {
+        use std::io::Write;
+
+        let mut child = std::process::Command::new("dot")
+            .arg(format!("-T{}", self.format.extension()))
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
             .map_err(|err| {
-                anyhow::format_err!(
+                format_err!(
                     "Failed to execute 'dot' command of Graphviz project: {}",
                     err
                 )
             })?;
 
-        Ok(svg_file)
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| format_err!("Failed to open stdin of 'dot' command"))?;
+        let writer = std::thread::spawn(move || stdin.write_all(source.as_bytes()));
+
+        let output = child.wait_with_output()?;
+        let written = writer
+            .join()
+            .map_err(|_| format_err!("Failed to write graph to 'dot' command"))?;
+
+        if !output.status.success() {
+            bail!(
+                "Graphviz 'dot' command failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+        written?;
+
+        Ok(output.stdout)
+    }
+
+    /// Graph in the configured format with filtering and collapsing options applied
+    pub fn render(
+        &self,
+        data: &crate::report_data::ReportData,
+        highlights: &GraphHighlights,
+    ) -> Result<Vec<u8>> {
+        let (data, highlights) = &self.view(data, highlights);
+        let r = match self.format {
+            GraphFormat::Svg | GraphFormat::Png => {
+                self.run_dot(self.generate_dot(data, highlights)?)?
+            }
+            GraphFormat::Dot => self.generate_dot(data, highlights)?.into_bytes(),
+            GraphFormat::Mermaid => self.generate_mermaid(data, highlights)?.into_bytes(),
+            GraphFormat::PlantUml => self.generate_plantuml(data, highlights)?.into_bytes(),
+            GraphFormat::Json => self.generate_json(data, highlights)?.into_bytes(),
+        };
+        Ok(r)
+    }
+
+    pub fn make(
+        &self,
+        data: &crate::report_data::ReportData,
+        highlights: &GraphHighlights,
+    ) -> Result<tempfile::NamedTempFile> {
+        use std::io::Write;
+
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(&self.render(data, highlights)?)?;
+
+        Ok(file)
+    }
+
+    pub fn attachment_name(&self) -> String {
+        format!("dependency_graph.{}", self.format.extension())
+    }
+
+    /// Confluence wiki markup showing the graph attached with [`Self::attachment_name`]
+    pub fn confluence_wiki(
+        &self,
+        data: &crate::report_data::ReportData,
+        highlights: &GraphHighlights,
+    ) -> Result<String> {
+        let (data, highlights) = &self.view(data, highlights);
+        let r = match self.format {
+            GraphFormat::Svg | GraphFormat::Png => format!("!{}!", self.attachment_name()),
+            GraphFormat::Mermaid => {
+                format!(
+                    "{{mermaid}}\n{}{{mermaid}}",
+                    self.generate_mermaid(data, highlights)?
+                )
+            }
+            GraphFormat::PlantUml => {
+                format!(
+                    "{{plantuml}}\n{}{{plantuml}}",
+                    self.generate_plantuml(data, highlights)?
+                )
+            }
+            GraphFormat::Dot | GraphFormat::Json => format!("[^{}]", self.attachment_name()),
+        };
+        Ok(r)
     }
 }
 
 // Color scheme: https://colorscheme.ru/#36422g0--w0w0
+
+#[cfg(test)]
+mod tests {
+    use super::{DependencyGraph, GraphHighlights};
+    use crate::report_data::test_data::{data, issue};
+
+    #[test]
+    fn dot_edges_are_sorted() {
+        let issues: Vec<_> = ["T-1", "T-2", "T-3", "T-4"]
+            .iter()
+            .map(|key| issue(key, None, None, None))
+            .collect();
+        let data = data(
+            &issues,
+            &[
+                ("T-3", "T-4"),
+                ("T-1", "T-2"),
+                ("T-2", "T-3"),
+                ("T-1", "T-3"),
+            ],
+        );
+        let graph: DependencyGraph = serde_yaml::from_str("{}").unwrap();
+        let dot = graph
+            .generate_dot(&data, &GraphHighlights::default())
+            .unwrap();
+        let edges: Vec<_> = dot
+            .lines()
+            .filter(|v| v.contains(" -> "))
+            .map(|v| v.split(" [").next().unwrap_or_default())
+            .collect();
+        let mut sorted = edges.clone();
+        sorted.sort();
+        assert_eq!(edges.len(), 4);
+        assert_eq!(edges, sorted);
+    }
+}
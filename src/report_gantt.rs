@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{bail, format_err, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::report::ReportIssue;
+use crate::report_data::IssueID;
+
+fn default_dependencies_deepness() -> usize {
+    1
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum GanttFormat {
+    #[default]
+    Svg,
+    /// Converted from SVG by `rsvg-convert`
+    Png,
+}
+
+impl GanttFormat {
+    pub fn extension(&self) -> &str {
+        match self {
+            GanttFormat::Svg => "svg",
+            GanttFormat::Png => "png",
+        }
+    }
+}
+
+impl FromStr for GanttFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "svg" => Ok(Self::Svg),
+            "png" => Ok(Self::Png),
+            v => Err(format!("Unknown Gantt chart format {:?}", v)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum GanttGroupBy {
+    #[default]
+    Epic,
+    Assignee,
+}
+
+impl FromStr for GanttGroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "epic" => Ok(Self::Epic),
+            "assignee" => Ok(Self::Assignee),
+            v => Err(format!("Unknown Gantt chart grouping {:?}", v)),
+        }
+    }
+}
+
+/// Bar of a single issue, dates are inclusive
+struct Bar<'a> {
+    issue: &'a ReportIssue,
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct GanttChart {
+    #[serde(default)]
+    pub group_by: GanttGroupBy,
+    #[serde(default)]
+    pub format: GanttFormat,
+}
+
+impl GanttChart {
+    fn xml_escape(s: &str) -> String {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    fn truncate(s: &str, length: usize) -> String {
+        if s.chars().count() > length {
+            format!("{}…", s.chars().take(length - 1).collect::<String>())
+        } else {
+            s.to_owned()
+        }
+    }
+
+    fn status_color(issue: &ReportIssue) -> &str {
+        match issue
+            .issue
+            .fields
+            .status
+            .as_ref()
+            .and_then(|v| v.status_category.as_ref())
+            .and_then(|v| v.key.as_deref())
+        {
+            Some("new") => "#8993A4",
+            Some("indeterminate") => "#2E56A6",
+            Some("done") => "#2E8B57",
+            _ => "#D50000",
+        }
+    }
+
+    /// Title of the issue group, None if the epic or assignee is unknown
+    fn group_title(
+        &self,
+        data: &crate::report_data::ReportData,
+        issue: &ReportIssue,
+    ) -> Option<String> {
+        match self.group_by {
+            GanttGroupBy::Epic => issue
+                .custom_fields
+                .epic_link
+                .as_ref()
+                .and_then(|key| data.epics.get(&issue.jira, key))
+                .map(|epic| format!("ЭПИК: {}", epic.issue.fields.summary)),
+            GanttGroupBy::Assignee => issue
+                .issue
+                .fields
+                .assignee
+                .as_ref()
+                .and_then(|v| v.display_name.clone()),
+        }
+    }
+
+    fn no_group_title(&self) -> &'static str {
+        match self.group_by {
+            GanttGroupBy::Epic => "Без эпика",
+            GanttGroupBy::Assignee => "Не назначено",
+        }
+    }
+
+    fn bar(issue: &ReportIssue) -> Option<Bar<'_>> {
+        let (start, end) = match (
+            issue.custom_fields.planned_start,
+            issue.custom_fields.planned_end,
+        ) {
+            (None, None) => return None,
+            (Some(start), None) => (start, start),
+            (None, Some(end)) => (end, end),
+            (Some(start), Some(end)) => (start, end.max(start)),
+        };
+        Some(Bar {
+            issue,
+            start: issue.jira.dates.date(start),
+            end: issue.jira.dates.date(end),
+        })
+    }
+
+    /// Issues shown on the chart. Epics are shown as groups
+    fn issues(data: &crate::report_data::ReportData) -> impl Iterator<Item = &ReportIssue> {
+        data.issues
+            .all()
+            .values()
+            .filter(|issue| issue.entity_type != crate::report::ReportIssueType::Epic)
+    }
+
+    /// Issues without planned dates which are not drawn
+    pub fn unscheduled(data: &crate::report_data::ReportData) -> Vec<&ReportIssue> {
+        let mut issues: Vec<_> = Self::issues(data)
+            .filter(|issue| Self::bar(issue).is_none())
+            .collect();
+        issues.sort_by(|a, b| a.issue.key.cmp(&b.issue.key));
+        issues
+    }
+
+    /// Relations ordering issues in time, others like mentions aren't scheduling constraints
+    fn dependencies(
+        data: &crate::report_data::ReportData,
+    ) -> impl Iterator<Item = &crate::report_data::Relation> {
+        data.relations.iter().filter(|v| v.kind.ordering)
+    }
+
+    /// Bars grouped by epic or assignee, groups without epic or assignee go last
+    fn groups<'a>(&self, data: &'a crate::report_data::ReportData) -> Vec<(String, Vec<Bar<'a>>)> {
+        let mut groups: HashMap<_, Vec<_>> = HashMap::new();
+        for bar in Self::issues(data).filter_map(Self::bar) {
+            let title = self.group_title(data, bar.issue);
+            let key = match title {
+                Some(title) => (false, title),
+                None => (true, self.no_group_title().to_owned()),
+            };
+            groups.entry(key).or_default().push(bar)
+        }
+        let mut groups: Vec<_> = groups.into_iter().collect();
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+        groups
+            .into_iter()
+            .map(|((_, title), mut bars)| {
+                bars.sort_by(|a, b| {
+                    (a.start, &a.issue.issue.key).cmp(&(b.start, &b.issue.issue.key))
+                });
+                (title, bars)
+            })
+            .collect()
+    }
+
+    pub fn generate_svg(&self, data: &crate::report_data::ReportData) -> Result<String> {
+        const LABEL_WIDTH: f64 = 320.0;
+        const CHART_WIDTH: f64 = 900.0;
+        const MARGIN: f64 = 20.0;
+        const HEADER: f64 = 40.0;
+        const ROW: f64 = 22.0;
+
+        let groups = self.groups(data);
+        let bars = groups.iter().flat_map(|(_, bars)| bars);
+        // Dates are shared by all Jira instances, so any issue has them
+        let today = match data.issues.all().values().next() {
+            None => crate::jira::DateDisplay::default().today(),
+            Some(v) => v.jira.dates.today(),
+        };
+        let first = bars.clone().map(|v| v.start).min().unwrap_or(today);
+        let last = bars.map(|v| v.end).max().unwrap_or(today);
+        let days = ((last - first).num_days() + 1) as f64;
+        let day_width = CHART_WIDTH / days;
+        let x = |date: chrono::NaiveDate| {
+            LABEL_WIDTH + MARGIN + (date - first).num_days() as f64 * day_width
+        };
+
+        // Row of every bar and group header
+        let mut rows = HashMap::new();
+        let mut row = 0;
+        for (_, bars) in &groups {
+            row += 1;
+            for bar in bars {
+                let _ = rows.insert(IssueID::of_issue(bar.issue), row);
+                row += 1;
+            }
+        }
+        let y = |row: usize| HEADER + row as f64 * ROW;
+        let height = y(row) + MARGIN;
+        let width = LABEL_WIDTH + CHART_WIDTH + 2.0 * MARGIN;
+
+        let mut output = String::new();
+        writeln!(
+            &mut output,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" font-family=\"sans-serif\" font-size=\"11\">"
+        )?;
+        writeln!(
+            &mut output,
+            "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>"
+        )?;
+
+        let mut colors: Vec<_> = Self::dependencies(data)
+            .map(|v| v.kind.style.color.as_str())
+            .collect();
+        colors.sort();
+        colors.dedup();
+        writeln!(&mut output, "<defs>")?;
+        for (n, color) in colors.iter().enumerate() {
+            writeln!(
+                &mut output,
+                "<marker id=\"arrow{n}\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"{}\"/></marker>",
+                Self::xml_escape(color)
+            )?;
+        }
+        writeln!(&mut output, "</defs>")?;
+
+        // Date grid, about fifteen labels
+        let label_step = (days as i64 / 15).max(1);
+        let mut date = first;
+        while date <= last {
+            writeln!(
+                &mut output,
+                "<line x1=\"{0:.1}\" y1=\"{1}\" x2=\"{0:.1}\" y2=\"{2}\" stroke=\"#E0E0E0\"/><text x=\"{0:.1}\" y=\"{3}\" text-anchor=\"middle\">{4}</text>",
+                x(date),
+                HEADER,
+                height - MARGIN,
+                HEADER - 8.0,
+                date.format("%m-%d")
+            )?;
+            date += chrono::Duration::days(label_step);
+        }
+
+        let mut row = 0;
+        for (title, bars) in &groups {
+            writeln!(
+                &mut output,
+                "<rect x=\"0\" y=\"{:.1}\" width=\"{width}\" height=\"{ROW}\" fill=\"#C0D5FF\" fill-opacity=\"0.4\"/><text x=\"{MARGIN}\" y=\"{:.1}\" font-weight=\"bold\">{}</text>",
+                y(row),
+                y(row) + 15.0,
+                Self::xml_escape(&Self::truncate(title, 80))
+            )?;
+            row += 1;
+            for bar in bars {
+                let issue = bar.issue;
+                let external =
+                    issue.entity_type == crate::report::ReportIssueType::ExternalDependency;
+                writeln!(
+                    &mut output,
+                    "<text x=\"{MARGIN}\" y=\"{:.1}\">{}{} {}</text>",
+                    y(row) + 15.0,
+                    if external { "↗ " } else { "" },
+                    Self::xml_escape(&issue.issue.key),
+                    Self::xml_escape(&Self::truncate(&issue.issue.fields.summary, 40))
+                )?;
+                writeln!(
+                    &mut output,
+                    "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"14\" rx=\"3\" fill=\"{}\"{}><title>{}: {}</title></rect>",
+                    x(bar.start),
+                    y(row) + 4.0,
+                    (x(bar.end) - x(bar.start) + day_width).max(2.0),
+                    Self::status_color(issue),
+                    if external {
+                        " fill-opacity=\"0.5\" stroke=\"black\" stroke-dasharray=\"3,2\""
+                    } else {
+                        ""
+                    },
+                    Self::xml_escape(&issue.custom_fields.plan(&issue.jira.dates)),
+                    Self::xml_escape(
+                        issue
+                            .issue
+                            .fields
+                            .status
+                            .as_ref()
+                            .and_then(|v| v.name.as_deref())
+                            .unwrap_or_default()
+                    )
+                )?;
+                row += 1;
+            }
+        }
+
+        // Arrows from the end of the source bar to the beginning of the target bar
+        let bars: HashMap<_, _> = groups
+            .iter()
+            .flat_map(|(_, bars)| bars)
+            .map(|v| (IssueID::of_issue(v.issue), v))
+            .collect();
+        let mut relations: Vec<_> = Self::dependencies(data).collect();
+        relations.sort_by_key(|v| (v.from.as_string(), v.to.as_string()));
+        for relation in relations {
+            let (from, to) = match (bars.get(&relation.from), bars.get(&relation.to)) {
+                (Some(from), Some(to)) => (from, to),
+                _ => continue,
+            };
+            let marker = colors
+                .iter()
+                .position(|v| *v == relation.kind.style.color)
+                .unwrap_or_default();
+            let x1 = x(from.end) + day_width;
+            let y1 = y(rows[&relation.from]) + 11.0;
+            let x2 = x(to.start);
+            let y2 = y(rows[&relation.to]) + 11.0;
+            writeln!(
+                &mut output,
+                "<path d=\"M {x1:.1} {y1:.1} h 6 V {y2:.1} H {x2:.1}\" fill=\"none\" stroke=\"{}\" stroke-width=\"1.2\"{} marker-end=\"url(#arrow{marker})\"/>",
+                Self::xml_escape(&relation.kind.style.color),
+                match relation.kind.style.line {
+                    crate::report::LineStyle::Dashed => " stroke-dasharray=\"5,3\"",
+                    crate::report::LineStyle::Dotted => " stroke-dasharray=\"1,3\"",
+                    _ => "",
+                }
+            )?;
+        }
+
+        if first <= today && today <= last {
+            writeln!(
+                &mut output,
+                "<line x1=\"{0:.1}\" y1=\"{1}\" x2=\"{0:.1}\" y2=\"{2}\" stroke=\"#D50000\" stroke-width=\"2\" stroke-dasharray=\"6,3\"/><text x=\"{0:.1}\" y=\"{3}\" text-anchor=\"middle\" fill=\"#D50000\">Сегодня</text>",
+                x(today) + day_width / 2.0,
+                HEADER,
+                height - MARGIN,
+                HEADER - 24.0,
+            )?;
+        }
+
+        writeln!(&mut output, "</svg>")?;
+        Ok(output)
+    }
+
+    fn run_converter(&self, source: String) -> Result<Vec<u8>> {
+        use std::io::Write;
+
+        let mut child = std::process::Command::new("rsvg-convert")
+            .args(["--format", self.format.extension()])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|err| format_err!("Failed to execute 'rsvg-convert' command: {}", err))?;
+
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| format_err!("Failed to open stdin of 'rsvg-convert' command"))?;
+        let writer = std::thread::spawn(move || stdin.write_all(source.as_bytes()));
+
+        let output = child.wait_with_output()?;
+        let written = writer
+            .join()
+            .map_err(|_| format_err!("Failed to write chart to 'rsvg-convert' command"))?;
+
+        if !output.status.success() {
+            bail!(
+                "'rsvg-convert' command failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+        written?;
+
+        Ok(output.stdout)
+    }
+
+    /// Chart in the configured format
+    pub fn render(&self, data: &crate::report_data::ReportData) -> Result<Vec<u8>> {
+        let svg = self.generate_svg(data)?;
+        match self.format {
+            GanttFormat::Svg => Ok(svg.into_bytes()),
+            GanttFormat::Png => self.run_converter(svg),
+        }
+    }
+
+    pub fn attachment_name(&self) -> String {
+        format!("gantt.{}", self.format.extension())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Gantt {
+    query_set: crate::report::QuerySet,
+    confluence: crate::confluence::ConfluenceServer,
+    space: String,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    /// Recursion depth for dependencies drawn on the chart
+    #[serde(default = "default_dependencies_deepness")]
+    dependencies_deepness: usize,
+    #[serde(default)]
+    foreign_relations: Vec<crate::report::ForeignRelation>,
+    #[serde(default)]
+    chart: GanttChart,
+}
+
+impl Gantt {
+    /// Confluence page updated by the report
+    pub fn page(&self) -> crate::report::ReportPage<'_> {
+        crate::report::ReportPage {
+            confluence: &self.confluence,
+            space: &self.space,
+            title: &self.title,
+        }
+    }
+
+    /// Jira queries of the report
+    pub fn queries(&self) -> Vec<&crate::config::JiraQuery> {
+        self.query_set.queries().iter().collect()
+    }
+
+    pub fn foreign_relations(&self) -> &[crate::report::ForeignRelation] {
+        &self.foreign_relations
+    }
+
+    pub fn generate(&self, data: &crate::report_data::ReportData) -> Result<String> {
+        let mut output = String::new();
+
+        if let Some(description) = &self.description {
+            writeln!(&mut output, "{}", description)?
+        }
+
+        writeln!(&mut output, "\nh1. Диаграмма Ганта\n")?;
+        writeln!(&mut output, "!{}!", self.chart.attachment_name())?;
+
+        let unscheduled = GanttChart::unscheduled(data);
+        if !unscheduled.is_empty() {
+            writeln!(&mut output, "\nh1. Без сроков\n")?;
+            writeln!(&mut output, "|| Описание таска || Jira-таск ||")?;
+            for issue in unscheduled {
+                writeln!(
+                    &mut output,
+                    "| {} | {} |",
+                    crate::confluence::wiki_escape(&issue.issue.fields.summary),
+                    issue.confluence_wiki_url(false)
+                )?
+            }
+        }
+
+        Ok(output)
+    }
+
+    pub async fn make(&self, config: Arc<crate::config::Config>) -> Result<()> {
+        use std::io::Write;
+
+        let issues_list = self.query_set.get_issues(config).await?;
+        let data = crate::report_data::ReportData::of_slice(
+            &self.foreign_relations,
+            &issues_list,
+            self.dependencies_deepness,
+            0,
+        )
+        .await?;
+
+        let wiki_content = self.generate(&data)?;
+
+        let mut chart = tempfile::NamedTempFile::new()?;
+        chart.write_all(&self.chart.render(&data)?)?;
+
+        let current_content = self.confluence.get_page(&self.space, &self.title).await?;
+
+        let id: u64 = current_content.id.parse()?;
+
+        self.confluence
+            .upload_attachment(id, chart.path(), &self.chart.attachment_name())
+            .await?;
+
+        let _result = self
+            .confluence
+            .update_content(
+                id,
+                crate::confluence_content_update::UpdateContentBody {
+                    version: crate::confluence_content_update::UpdateContentBodyVersion {
+                        number: current_content.version.number + 1,
+                    },
+                    title: current_content.title.clone(),
+                    content_type: crate::confluence_types::ContentType::Page,
+                    body: crate::confluence_types::ContentBody {
+                        storage: crate::confluence_types::ContentBodyStorage {
+                            value: wiki_content,
+                            representation: crate::confluence_types::ContentRepresentation::Wiki,
+                        },
+                    },
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GanttChart, GanttFormat, GanttGroupBy};
+    use crate::report_data::test_data::{data, issue};
+
+    #[test]
+    fn issues_of_unknown_epic_share_group_without_epic() {
+        let mut with_link = issue("T-1", Some("2022-10-03"), None, None);
+        with_link.custom_fields.epic_link = Some("T-100".to_owned());
+        let without_link = issue("T-2", Some("2022-10-04"), None, None);
+        let data = data(&[with_link, without_link], &[]);
+        let chart = GanttChart {
+            group_by: GanttGroupBy::Epic,
+            format: GanttFormat::default(),
+        };
+        let groups = chart.groups(&data);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].0, "Без эпика");
+        assert_eq!(groups[0].1.len(), 2);
+    }
+
+    #[test]
+    fn only_ordering_relations_are_drawn() {
+        let issues = [
+            issue("T-1", Some("2022-10-03"), Some("2022-10-04"), None),
+            issue("T-2", Some("2022-10-05"), Some("2022-10-06"), None),
+            issue("T-3", Some("2022-10-05"), Some("2022-10-07"), None),
+        ];
+        let mut data = data(&issues, &[("T-1", "T-2")]);
+        let mention = crate::report::default_relation_kinds()
+            .into_iter()
+            .find(|v| !v.ordering)
+            .unwrap();
+        data.relations.insert(crate::report_data::Relation {
+            from: crate::report_data::test_data::id("T-1"),
+            to: crate::report_data::test_data::id("T-3"),
+            kind: mention,
+        });
+        let svg = GanttChart::default().generate_svg(&data).unwrap();
+        assert_eq!(svg.matches("marker-end").count(), 1);
+    }
+}
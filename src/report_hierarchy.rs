@@ -0,0 +1,126 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use crate::report_data::IssueID;
+
+/// Resolved issues among all descendants of a hierarchy parent
+pub struct Progress {
+    pub id: IssueID,
+    /// Distance from the root of the hierarchy
+    pub level: usize,
+    pub total: usize,
+    pub done: usize,
+}
+
+impl Progress {
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.done as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+pub struct Hierarchy<'a> {
+    data: &'a crate::report_data::ReportData,
+    children: HashMap<&'a IssueID, Vec<&'a IssueID>>,
+}
+
+impl<'a> Hierarchy<'a> {
+    pub fn new(data: &'a crate::report_data::ReportData) -> Self {
+        let mut children: HashMap<_, Vec<_>> = HashMap::new();
+        for relation in &data.hierarchy {
+            children
+                .entry(&relation.parent)
+                .or_default()
+                .push(&relation.child);
+        }
+        for list in children.values_mut() {
+            list.sort_by_key(|v| v.as_string());
+        }
+        Self { data, children }
+    }
+
+    fn descendants(&self, id: &'a IssueID) -> HashSet<&'a IssueID> {
+        let mut result = HashSet::new();
+        let mut queue = vec![id];
+        while let Some(id) = queue.pop() {
+            for child in self
+                .children
+                .get(id)
+                .map(|v| v.as_slice())
+                .unwrap_or_default()
+            {
+                if result.insert(*child) {
+                    queue.push(child)
+                }
+            }
+        }
+        let _ = result.remove(id);
+        result
+    }
+
+    /// Progress of every parent, depth-first from the roots of the hierarchy
+    pub fn progress(&self) -> Vec<Progress> {
+        let child_ids: HashSet<_> = self.children.values().flatten().collect();
+        let mut roots: Vec<_> = self
+            .children
+            .keys()
+            .filter(|v| !child_ids.contains(v))
+            .copied()
+            .collect();
+        roots.sort_by_key(|v| v.as_string());
+
+        let mut result = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack: Vec<_> = roots.into_iter().rev().map(|v| (v, 0)).collect();
+        while let Some((id, level)) = stack.pop() {
+            if !self.children.contains_key(id) || !visited.insert(id) {
+                continue;
+            }
+            let descendants = self.descendants(id);
+            result.push(Progress {
+                id: id.clone(),
+                level,
+                total: descendants.len(),
+                done: descendants
+                    .iter()
+                    .filter_map(|v| self.data.issues.all().get(v))
+                    .filter(|v| v.resolved_at().is_some())
+                    .count(),
+            });
+            for child in self.children[id].iter().rev() {
+                stack.push((child, level + 1))
+            }
+        }
+        result
+    }
+
+    pub fn confluence_wiki(&self) -> Result<String> {
+        let mut output = String::new();
+        writeln!(
+            &mut output,
+            "|| Описание || Jira-таск || Задач || Готово || Прогресс ||"
+        )?;
+        for progress in self.progress() {
+            let issue = match self.data.issues.all().get(&progress.id) {
+                None => continue,
+                Some(v) => v,
+            };
+            writeln!(
+                &mut output,
+                "| {}{} | {} | {} | {} | {:.0}% |",
+                "— ".repeat(progress.level),
+                crate::confluence::wiki_escape(&issue.issue.fields.summary),
+                issue.confluence_wiki_url(false),
+                progress.total,
+                progress.done,
+                progress.percent()
+            )?
+        }
+        Ok(output)
+    }
+}
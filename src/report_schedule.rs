@@ -0,0 +1,289 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use crate::report::ReportIssue;
+use crate::report_data::IssueID;
+
+#[derive(PartialEq, Debug)]
+pub enum ScheduleProblemKind {
+    /// Planned end is before planned start
+    EndBeforeStart,
+    /// Issue is planned to start before its dependency is planned to end
+    StartsBeforeDependency,
+    /// Dependency was resolved after the issue was planned to start
+    DependencyDoneLate,
+    /// Unresolved dependency of a scheduled issue has no planned end
+    DependencyUnscheduled,
+}
+
+pub struct ScheduleProblem {
+    pub issue: IssueID,
+    pub dependency: Option<IssueID>,
+    pub kind: ScheduleProblemKind,
+}
+
+impl ScheduleProblem {
+    fn format_date(
+        issue: Option<&crate::report::ReportIssue>,
+        date: impl Fn(&crate::report::ReportIssue) -> Option<chrono::DateTime<chrono::Utc>>,
+    ) -> String {
+        issue
+            .and_then(|v| Some(v.jira.dates.format_date(date(v)?)))
+            .unwrap_or_else(|| "?".to_owned())
+    }
+
+    /// Planned start, planned end, dependency planned end and dependency resolution
+    fn dates(&self, data: &crate::report_data::ReportData) -> (String, String, String, String) {
+        let issue = data.issues.all().get(&self.issue);
+        let dependency = self
+            .dependency
+            .as_ref()
+            .and_then(|v| data.issues.all().get(v));
+        (
+            Self::format_date(issue, |v| v.custom_fields.planned_start),
+            Self::format_date(issue, |v| v.custom_fields.planned_end),
+            Self::format_date(dependency, |v| v.custom_fields.planned_end),
+            Self::format_date(dependency, |v| v.resolved_at()),
+        )
+    }
+
+    pub fn as_string(&self, data: &crate::report_data::ReportData) -> String {
+        let (start, end, dependency_end, dependency_resolved) = self.dates(data);
+        match self.kind {
+            ScheduleProblemKind::EndBeforeStart => {
+                format!("planned end {} is before planned start {}", end, start)
+            }
+            ScheduleProblemKind::StartsBeforeDependency => format!(
+                "planned start {} is before dependency planned end {}",
+                start, dependency_end
+            ),
+            ScheduleProblemKind::DependencyDoneLate => format!(
+                "dependency resolved {} after planned start {}",
+                dependency_resolved, start
+            ),
+            ScheduleProblemKind::DependencyUnscheduled => {
+                "dependency has no planned end".to_owned()
+            }
+        }
+    }
+
+    fn confluence_wiki_description(&self, data: &crate::report_data::ReportData) -> String {
+        let (start, end, dependency_end, dependency_resolved) = self.dates(data);
+        match self.kind {
+            ScheduleProblemKind::EndBeforeStart => {
+                format!("Окончание {} раньше начала {}", end, start)
+            }
+            ScheduleProblemKind::StartsBeforeDependency => format!(
+                "Начало {} раньше окончания зависимости {}",
+                start, dependency_end
+            ),
+            ScheduleProblemKind::DependencyDoneLate => format!(
+                "Зависимость закрыта {} позже начала {}",
+                dependency_resolved, start
+            ),
+            ScheduleProblemKind::DependencyUnscheduled => {
+                "У зависимости не запланировано окончание".to_owned()
+            }
+        }
+    }
+}
+
+pub struct ScheduleCheck {
+    pub problems: Vec<ScheduleProblem>,
+    /// Ordering relations with problems as (from, to) pairs
+    pub relations: HashSet<(IssueID, IssueID)>,
+}
+
+impl ScheduleCheck {
+    fn check_relation(
+        issue: &ReportIssue,
+        dependency: &ReportIssue,
+    ) -> Option<ScheduleProblemKind> {
+        let start = issue.custom_fields.planned_start;
+        match dependency.resolved_at() {
+            Some(resolved) => match start {
+                Some(start) if resolved > start => Some(ScheduleProblemKind::DependencyDoneLate),
+                _ => None,
+            },
+            // Unscheduled dependency matters only for issues scheduled themselves
+            None => match (start, dependency.custom_fields.planned_end) {
+                (Some(_), None) => Some(ScheduleProblemKind::DependencyUnscheduled),
+                (Some(start), Some(end)) if start < end => {
+                    Some(ScheduleProblemKind::StartsBeforeDependency)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Compares planned dates of issues with planned dates and resolutions of their dependencies
+    pub fn find(data: &crate::report_data::ReportData) -> Self {
+        let mut problems = Vec::new();
+        let mut relations = HashSet::new();
+
+        for (id, issue) in data.issues.all() {
+            if let (Some(start), Some(end)) = (
+                issue.custom_fields.planned_start,
+                issue.custom_fields.planned_end,
+            ) {
+                if end < start {
+                    problems.push(ScheduleProblem {
+                        issue: id.clone(),
+                        dependency: None,
+                        kind: ScheduleProblemKind::EndBeforeStart,
+                    })
+                }
+            }
+        }
+
+        for relation in &data.relations {
+            if !relation.kind.ordering || relation.from == relation.to {
+                continue;
+            }
+            let (dependency, issue) = match (
+                data.issues.all().get(&relation.from),
+                data.issues.all().get(&relation.to),
+            ) {
+                (Some(from), Some(to)) => (from, to),
+                _ => continue,
+            };
+            if let Some(kind) = Self::check_relation(issue, dependency) {
+                let _ = relations.insert((relation.from.clone(), relation.to.clone()));
+                problems.push(ScheduleProblem {
+                    issue: relation.to.clone(),
+                    dependency: Some(relation.from.clone()),
+                    kind,
+                })
+            }
+        }
+
+        problems.sort_by_key(|v| {
+            (
+                v.issue.as_string(),
+                v.dependency.as_ref().map(|v| v.as_string()),
+            )
+        });
+
+        Self {
+            problems,
+            relations,
+        }
+    }
+
+    pub fn confluence_wiki(&self, data: &crate::report_data::ReportData) -> Result<String> {
+        let link = |id: &IssueID| match data.issues.all().get(id) {
+            None => id.issue.clone(),
+            Some(issue) => issue.confluence_wiki_url(false),
+        };
+
+        let mut output = String::new();
+        writeln!(&mut output, "|| Jira-таск || Зависимость || Проблема ||")?;
+        for problem in &self.problems {
+            writeln!(
+                &mut output,
+                "| {} | {} | {} |",
+                link(&problem.issue),
+                problem.dependency.as_ref().map(link).unwrap_or_default(),
+                problem.confluence_wiki_description(data)
+            )?
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScheduleCheck, ScheduleProblemKind};
+    use crate::report_data::test_data::{data, id, issue};
+
+    /// Problems as (issue, dependency, kind)
+    fn problems(
+        issues: &[crate::report::ReportIssue],
+        relations: &[(&str, &str)],
+    ) -> Vec<(String, Option<String>, ScheduleProblemKind)> {
+        ScheduleCheck::find(&data(issues, relations))
+            .problems
+            .into_iter()
+            .map(|v| (v.issue.issue, v.dependency.map(|v| v.issue), v.kind))
+            .collect()
+    }
+
+    #[test]
+    fn end_before_start() {
+        let issues = [issue("T-1", Some("2022-10-05"), Some("2022-10-03"), None)];
+        assert_eq!(
+            problems(&issues, &[]),
+            vec![("T-1".to_owned(), None, ScheduleProblemKind::EndBeforeStart)]
+        );
+    }
+
+    #[test]
+    fn starts_before_dependency() {
+        let issues = [
+            issue("T-1", Some("2022-10-01"), Some("2022-10-10"), None),
+            issue("T-2", Some("2022-10-05"), Some("2022-10-12"), None),
+        ];
+        assert_eq!(
+            problems(&issues, &[("T-1", "T-2")]),
+            vec![(
+                "T-2".to_owned(),
+                Some("T-1".to_owned()),
+                ScheduleProblemKind::StartsBeforeDependency
+            )]
+        );
+        let check = ScheduleCheck::find(&data(&issues, &[("T-1", "T-2")]));
+        assert!(check.relations.contains(&(id("T-1"), id("T-2"))));
+    }
+
+    #[test]
+    fn dependency_done_late() {
+        let issues = [
+            issue("T-1", None, None, Some("2022-10-07T10:00:00.000+0000")),
+            issue("T-2", Some("2022-10-05"), Some("2022-10-12"), None),
+        ];
+        assert_eq!(
+            problems(&issues, &[("T-1", "T-2")]),
+            vec![(
+                "T-2".to_owned(),
+                Some("T-1".to_owned()),
+                ScheduleProblemKind::DependencyDoneLate
+            )]
+        );
+    }
+
+    #[test]
+    fn dependency_done_in_time() {
+        let issues = [
+            issue("T-1", None, None, Some("2022-10-03T10:00:00.000+0000")),
+            issue("T-2", Some("2022-10-05"), Some("2022-10-12"), None),
+        ];
+        assert!(problems(&issues, &[("T-1", "T-2")]).is_empty());
+    }
+
+    #[test]
+    fn dependency_unscheduled() {
+        let issues = [
+            issue("T-1", None, None, None),
+            issue("T-2", Some("2022-10-05"), Some("2022-10-12"), None),
+        ];
+        assert_eq!(
+            problems(&issues, &[("T-1", "T-2")]),
+            vec![(
+                "T-2".to_owned(),
+                Some("T-1".to_owned()),
+                ScheduleProblemKind::DependencyUnscheduled
+            )]
+        );
+    }
+
+    #[test]
+    fn unscheduled_issue_with_unscheduled_dependency() {
+        let issues = [
+            issue("T-1", None, None, None),
+            issue("T-2", None, None, None),
+        ];
+        assert!(problems(&issues, &[("T-1", "T-2")]).is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{bail, Result};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fmt::Write, sync::Arc};
 
@@ -10,6 +10,11 @@ pub enum GroupBy {
     Assignee,
     Epic,
     Label,
+    Status,
+    CustomField(String),
+    /// Field alias from `fields` of the Jira instance, issues with several values are counted
+    /// in every group
+    Field(String),
 }
 
 impl GroupBy {
@@ -19,6 +24,9 @@ impl GroupBy {
             GroupBy::Assignee => "Исполнитель",
             GroupBy::Epic => "Эпик",
             GroupBy::Label => "Метка",
+            GroupBy::Status => "Статус",
+            GroupBy::CustomField(v) => v,
+            GroupBy::Field(v) => v,
         }
     }
 
@@ -50,6 +58,21 @@ impl GroupBy {
                 .map(|v| v.confluence_wiki_epic_url())
                 .unwrap_or_default()],
             GroupBy::Label => issue.issue.fields.labels.clone().unwrap_or_default(),
+            GroupBy::Status => vec![issue
+                .issue
+                .fields
+                .status
+                .as_ref()
+                .and_then(|v| v.name.clone())
+                .unwrap_or("не определен".to_owned())],
+            GroupBy::CustomField(v) => vec![issue.custom_field_str(v).unwrap_or_default()],
+            GroupBy::Field(v) => match issue.field_value(v) {
+                None => vec![String::new()],
+                Some(serde_json::Value::Array(v)) if !v.is_empty() => {
+                    v.iter().map(crate::jira::field_text).collect()
+                }
+                Some(v) => vec![crate::jira::field_text(&v)],
+            },
         }
     }
 }
@@ -57,6 +80,7 @@ impl GroupBy {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Member {
     name: String,
+    /// Field alias or ID of custom field
     story_points_field: String,
     group_by: GroupBy,
     query_set: crate::report::QuerySet,
@@ -71,7 +95,7 @@ pub struct MemberResult {
 
 impl MemberResult {
     pub async fn generate(&self) -> Result<String> {
-        let data = crate::report_data::ReportData::of_slice(&[], &self.issues, 0).await?;
+        let data = crate::report_data::ReportData::of_slice(&[], &self.issues, 0, 0).await?;
 
         let mut output = String::new();
 
@@ -89,7 +113,7 @@ impl MemberResult {
         for issue in &self.issues {
             let titles = self.member.group_by.get_titles(issue, &data);
             let story_points = issue
-                .custom_field_f64(&self.member.story_points_field)
+                .field_f64(&self.member.story_points_field)
                 .unwrap_or_default();
             for title in titles {
                 let ent = sums.entry(title).or_insert(0);
@@ -119,6 +143,23 @@ pub struct StoryPoints {
 }
 
 impl StoryPoints {
+    /// Confluence page updated by the report
+    pub fn page(&self) -> crate::report::ReportPage<'_> {
+        crate::report::ReportPage {
+            confluence: &self.confluence,
+            space: &self.space,
+            title: &self.title,
+        }
+    }
+
+    /// Jira queries of all members
+    pub fn queries(&self) -> Vec<&crate::config::JiraQuery> {
+        self.members
+            .iter()
+            .flat_map(|v| v.query_set.queries())
+            .collect()
+    }
+
     pub async fn make(&self, config: Arc<crate::config::Config>) -> Result<()> {
         let mut join_set = tokio::task::JoinSet::new();
         for member in &self.members {
@@ -150,16 +191,7 @@ impl StoryPoints {
             writeln!(&mut wiki_content, "{}", member_result.generate().await?)?
         }
 
-        let get_result = self
-            .confluence
-            .get_content(&self.space, &self.title)
-            .await
-            .unwrap();
-
-        let current_content = match get_result.results.first() {
-            None => bail!("Page not found"),
-            Some(v) => v,
-        };
+        let current_content = self.confluence.get_page(&self.space, &self.title).await?;
 
         let id: u64 = current_content.id.parse()?;
 
@@ -0,0 +1,274 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt::Write, sync::Arc};
+
+use crate::report::{ReportIssue, SortValue};
+use crate::report_storypoints::GroupBy;
+use crate::report_worklog::ExtraField;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Column {
+    name: String,
+    field: ExtraField,
+    /// Sum numeric values of the column for every group
+    #[serde(default)]
+    subtotal: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SortKey {
+    field: ExtraField,
+    #[serde(default)]
+    descending: bool,
+}
+
+impl SortKey {
+    fn compare(&self, a: &SortValue, b: &SortValue) -> Ordering {
+        let r = a.compare(b);
+        if self.descending {
+            r.reverse()
+        } else {
+            r
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Member {
+    name: String,
+    query_set: crate::report::QuerySet,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+pub struct MemberResult {
+    member: Member,
+    issues: Vec<ReportIssue>,
+}
+
+impl MemberResult {
+    fn sort_issues(
+        &self,
+        report: &Table,
+        data: &crate::report_data::ReportData,
+        issues: &mut [&ReportIssue],
+    ) {
+        issues.sort_by(|a, b| {
+            report
+                .sort_by
+                .iter()
+                .map(|key| {
+                    key.compare(
+                        &key.field.sort_value(a, data),
+                        &key.field.sort_value(b, data),
+                    )
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    /// Typed value of the group title. Fields with single values are ordered by their type,
+    /// other groups by title
+    fn group_order(group_by: &GroupBy, issue: &ReportIssue, title: &str) -> SortValue {
+        let value = match group_by {
+            GroupBy::CustomField(v) | GroupBy::Field(v) => issue.sort_value(v),
+            _ => SortValue::Empty,
+        };
+        match value {
+            SortValue::Number(_) | SortValue::Moment(_) | SortValue::Key(_, _) => value,
+            _ => SortValue::Text(title.to_owned()),
+        }
+    }
+
+    fn write_table(
+        &self,
+        output: &mut String,
+        report: &Table,
+        data: &crate::report_data::ReportData,
+        issues: &[&ReportIssue],
+    ) -> Result<()> {
+        writeln!(
+            output,
+            "||{}",
+            report
+                .columns
+                .iter()
+                .map(|v| format!(" {} ||", v.name))
+                .collect::<Vec<_>>()
+                .join("")
+        )?;
+
+        let mut subtotals = vec![0f64; report.columns.len()];
+        for issue in issues {
+            write!(output, "|")?;
+            for (column, subtotal) in report.columns.iter().zip(subtotals.iter_mut()) {
+                if column.subtotal {
+                    if let SortValue::Number(v) = column.field.sort_value(issue, data) {
+                        *subtotal += v
+                    }
+                }
+                write!(output, " {} |", column.field.value(issue, data))?
+            }
+            writeln!(output)?;
+        }
+
+        if report.columns.iter().any(|v| v.subtotal) {
+            write!(output, "||")?;
+            for (n, (column, subtotal)) in report.columns.iter().zip(subtotals).enumerate() {
+                if column.subtotal {
+                    write!(output, " {subtotal} ||")?
+                } else if n == 0 {
+                    write!(output, " Итого ||")?
+                } else {
+                    write!(output, " ||")?
+                }
+            }
+            writeln!(output)?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn generate(&self, report: &Table) -> Result<String> {
+        let data = crate::report_data::ReportData::of_slice(&[], &self.issues, 0, 0).await?;
+
+        let mut output = String::new();
+
+        writeln!(&mut output, "\nh1. {}\n", self.member.name)?;
+        if let Some(description) = &self.member.description {
+            writeln!(&mut output, "{}", description)?
+        }
+
+        let mut issues: Vec<_> = self
+            .issues
+            .iter()
+            .filter(|issue| issue.entity_type == crate::report::ReportIssueType::ReportMember)
+            .collect();
+        self.sort_issues(report, &data, &mut issues);
+
+        match &report.group_by {
+            None => self.write_table(&mut output, report, &data, &issues)?,
+            Some(group_by) => {
+                let mut groups: Vec<(String, SortValue, Vec<&ReportIssue>)> = Vec::new();
+                for issue in issues {
+                    for title in group_by.get_titles(issue, &data) {
+                        match groups.iter_mut().find(|(v, _, _)| v == &title) {
+                            Some((_, _, list)) => list.push(issue),
+                            None => {
+                                let order = Self::group_order(group_by, issue, &title);
+                                groups.push((title, order, vec![issue]))
+                            }
+                        }
+                    }
+                }
+                groups.sort_by(|a, b| a.1.compare(&b.1));
+
+                for (title, _, issues) in groups {
+                    writeln!(
+                        &mut output,
+                        "\nh2. {}: {} ({} задач)\n",
+                        group_by.get_column_title(),
+                        title,
+                        issues.len()
+                    )?;
+                    self.write_table(&mut output, report, &data, &issues)?;
+                }
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Table {
+    confluence: crate::confluence::ConfluenceServer,
+    space: String,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    columns: Vec<Column>,
+    #[serde(default)]
+    sort_by: Vec<SortKey>,
+    #[serde(default)]
+    group_by: Option<GroupBy>,
+    members: Vec<Member>,
+}
+
+impl Table {
+    /// Confluence page updated by the report
+    pub fn page(&self) -> crate::report::ReportPage<'_> {
+        crate::report::ReportPage {
+            confluence: &self.confluence,
+            space: &self.space,
+            title: &self.title,
+        }
+    }
+
+    /// Jira queries of all members
+    pub fn queries(&self) -> Vec<&crate::config::JiraQuery> {
+        self.members
+            .iter()
+            .flat_map(|v| v.query_set.queries())
+            .collect()
+    }
+
+    pub async fn make(&self, config: Arc<crate::config::Config>) -> Result<()> {
+        let mut join_set = tokio::task::JoinSet::new();
+        for member in &self.members {
+            let member_clone = member.clone();
+            let config = config.clone();
+            let _abort_handle = join_set.spawn(async move {
+                let handler = member_clone.query_set.get_issues(config).await;
+                (handler, member_clone)
+            });
+        }
+
+        let mut members_results = Vec::new();
+        while let Some(pair) = join_set.join_next().await {
+            let (result, member) = pair?;
+            let issues = result?;
+            members_results.push(MemberResult { member, issues })
+        }
+
+        // Results are asyncronous so should be sorted for stable ordering
+        members_results.sort_by(|a, b| a.member.name.cmp(&b.member.name));
+
+        let mut wiki_content = String::new();
+
+        if let Some(description) = &self.description {
+            writeln!(&mut wiki_content, "{}", description)?
+        }
+
+        for member_result in &members_results {
+            writeln!(&mut wiki_content, "{}", member_result.generate(self).await?)?
+        }
+
+        let current_content = self.confluence.get_page(&self.space, &self.title).await?;
+
+        let id: u64 = current_content.id.parse()?;
+
+        let _result = self
+            .confluence
+            .update_content(
+                id,
+                crate::confluence_content_update::UpdateContentBody {
+                    version: crate::confluence_content_update::UpdateContentBodyVersion {
+                        number: current_content.version.number + 1,
+                    },
+                    title: current_content.title.clone(),
+                    content_type: crate::confluence_types::ContentType::Page,
+                    body: crate::confluence_types::ContentBody {
+                        storage: crate::confluence_types::ContentBodyStorage {
+                            value: wiki_content,
+                            representation: crate::confluence_types::ContentRepresentation::Wiki,
+                        },
+                    },
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::{bail, Result};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::{fmt::Write, sync::Arc};
 
@@ -11,14 +11,22 @@ pub struct ExtraFieldSetRecord {
 }
 
 impl ExtraFieldSetRecord {
-    pub fn value(&self, issue: &ReportIssue) -> String {
-        format!("{}{}", self.prefix, self.field.value(issue))
+    pub fn value(&self, issue: &ReportIssue, data: &crate::report_data::ReportData) -> String {
+        format!("{}{}", self.prefix, self.field.value(issue, data))
     }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub enum ExtraField {
+    Summary,
+    Key,
+    Epic,
+    Status,
+    Assignee,
+    Reporter,
     CustomField(String),
+    /// Field alias from `fields` of the Jira instance
+    Field(String),
     Schedule,
     Priority,
     Labels,
@@ -26,9 +34,40 @@ pub enum ExtraField {
 }
 
 impl ExtraField {
-    pub fn value(&self, issue: &ReportIssue) -> String {
+    pub fn value(&self, issue: &ReportIssue, data: &crate::report_data::ReportData) -> String {
         match self {
+            ExtraField::Summary => crate::confluence::wiki_escape(&issue.issue.fields.summary),
+            ExtraField::Key => issue.confluence_wiki_url(false),
+            ExtraField::Epic => issue
+                .custom_fields
+                .epic_link
+                .as_ref()
+                .and_then(|epic_key| data.epics.get(&issue.jira, epic_key))
+                .map(|v| v.confluence_wiki_epic_url())
+                .unwrap_or_default(),
+            ExtraField::Status => issue
+                .issue
+                .fields
+                .status
+                .as_ref()
+                .and_then(|v| v.name.clone())
+                .unwrap_or_default(),
+            ExtraField::Assignee => issue
+                .issue
+                .fields
+                .assignee
+                .as_ref()
+                .and_then(|v| v.display_name.clone())
+                .unwrap_or_default(),
+            ExtraField::Reporter => issue
+                .issue
+                .fields
+                .creator
+                .display_name
+                .clone()
+                .unwrap_or_default(),
             ExtraField::CustomField(v) => issue.custom_field_str(v).unwrap_or_default(),
+            ExtraField::Field(v) => issue.field_str(v).unwrap_or_default(),
             ExtraField::Schedule => issue.confluence_wiki_schedule(),
             ExtraField::Priority => issue
                 .issue
@@ -46,11 +85,39 @@ impl ExtraField {
                 .join(", "),
             ExtraField::Set(v) => v
                 .iter()
-                .map(|v| v.value(issue))
+                .map(|v| v.value(issue, data))
                 .collect::<Vec<_>>()
                 .join("\\\\"),
         }
     }
+
+    /// Typed value used to sort issues and sum columns
+    pub fn sort_value(
+        &self,
+        issue: &ReportIssue,
+        data: &crate::report_data::ReportData,
+    ) -> crate::report::SortValue {
+        use crate::report::SortValue;
+
+        match self {
+            ExtraField::Summary => SortValue::Text(issue.issue.fields.summary.clone()),
+            ExtraField::Key => SortValue::of_text(&issue.issue.key),
+            ExtraField::Epic => issue
+                .custom_fields
+                .epic_link
+                .as_ref()
+                .and_then(|epic_key| data.epics.get(&issue.jira, epic_key))
+                .map(|v| SortValue::Text(v.issue.fields.summary.clone()))
+                .unwrap_or(SortValue::Empty),
+            ExtraField::CustomField(v) | ExtraField::Field(v) => issue.sort_value(v),
+            ExtraField::Schedule => issue
+                .custom_fields
+                .planned_start
+                .or(issue.custom_fields.planned_end)
+                .map_or(SortValue::Empty, SortValue::Moment),
+            v => SortValue::of_text(&v.value(issue, data)),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -78,20 +145,10 @@ pub struct MemberResult {
 impl MemberResult {
     fn get_task(&self, issue: &crate::report::ReportIssue, report: &Worklog) -> String {
         let summary = match report.title_length_limit {
-            Some(v) => {
-                let indices = issue
-                    .issue
-                    .fields
-                    .summary
-                    .char_indices()
-                    .collect::<Vec<_>>();
-                if indices.len() > v {
-                    let end = indices.iter().map(|(i, _)| *i).nth(v).unwrap();
-                    format!("{} …", &issue.issue.fields.summary[..end])
-                } else {
-                    issue.issue.fields.summary.clone()
-                }
-            }
+            Some(v) => match issue.issue.fields.summary.char_indices().nth(v) {
+                Some((end, _)) => format!("{} …", &issue.issue.fields.summary[..end]),
+                None => issue.issue.fields.summary.clone(),
+            },
             None => issue.issue.fields.summary.clone(),
         };
 
@@ -137,7 +194,7 @@ impl MemberResult {
     }
 
     pub async fn generate(&self, report: &Worklog) -> Result<String> {
-        let data = crate::report_data::ReportData::of_slice(&[], &self.issues, 0).await?;
+        let data = crate::report_data::ReportData::of_slice(&[], &self.issues, 0, 0).await?;
 
         let mut output = String::new();
 
@@ -176,7 +233,7 @@ impl MemberResult {
             write!(&mut output, "| {col1} | {col2} | {col3} |")?;
 
             for extra_column in &report.extra_columns {
-                let value = extra_column.field.value(issue);
+                let value = extra_column.field.value(issue, &data);
                 write!(&mut output, " {value} |")?
             }
 
@@ -206,6 +263,23 @@ pub struct Worklog {
 }
 
 impl Worklog {
+    /// Confluence page updated by the report
+    pub fn page(&self) -> crate::report::ReportPage<'_> {
+        crate::report::ReportPage {
+            confluence: &self.confluence,
+            space: &self.space,
+            title: &self.title,
+        }
+    }
+
+    /// Jira queries of all members
+    pub fn queries(&self) -> Vec<&crate::config::JiraQuery> {
+        self.members
+            .iter()
+            .flat_map(|v| v.query_set.queries())
+            .collect()
+    }
+
     pub async fn make(&self, config: Arc<crate::config::Config>) -> Result<()> {
         let mut join_set = tokio::task::JoinSet::new();
         for member in &self.members {
@@ -236,16 +310,7 @@ impl Worklog {
             writeln!(&mut wiki_content, "{}", member_result.generate(self).await?)?
         }
 
-        let get_result = self
-            .confluence
-            .get_content(&self.space, &self.title)
-            .await
-            .unwrap();
-
-        let current_content = match get_result.results.first() {
-            None => bail!("Page not found"),
-            Some(v) => v,
-        };
+        let current_content = self.confluence.get_page(&self.space, &self.title).await?;
 
         let id: u64 = current_content.id.parse()?;
 